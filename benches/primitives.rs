@@ -0,0 +1,131 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rlwe_rs::{
+    params::{ParameterSet, PRECISION, RLWE1024, TAILCUT, TOY},
+    pke::KeyGen,
+    sampling::Sampling,
+    util::zzx::{self, ZZX},
+};
+use rug::Float;
+
+const PARAMETER_SETS: [ParameterSet; 2] = [TOY, RLWE1024];
+
+fn sampler(params: &ParameterSet) -> Sampling {
+    Sampling::new(
+        PRECISION,
+        TAILCUT,
+        Float::with_val(PRECISION, params.sigma),
+        Float::with_val(PRECISION, 0),
+    )
+}
+
+fn bench_sampling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sampling");
+    for params in PARAMETER_SETS {
+        let gauss = sampler(&params);
+        group.bench_function(BenchmarkId::new("knuth_yao", params.name), |b| {
+            b.iter(|| gauss.knuth_yao())
+        });
+        group.bench_function(
+            BenchmarkId::new("knuth_yao_constant_time", params.name),
+            |b| b.iter(|| gauss.knuth_yao_constant_time()),
+        );
+        // the probability matrix is built by the constructor
+        group.bench_function(
+            BenchmarkId::new("build_probability_matrix", params.name),
+            |b| b.iter(|| sampler(black_box(&params))),
+        );
+        // one polynomial of noise, drawn sample by sample or from one random stream
+        let bound = (TAILCUT * params.sigma).round() as i32;
+        group.bench_function(BenchmarkId::new("knuth_yao_poly", params.name), |b| {
+            b.iter(|| {
+                (0..params.p)
+                    .map(|_| loop {
+                        let x = gauss.knuth_yao();
+                        if x.abs() < bound {
+                            break x;
+                        }
+                    })
+                    .collect::<Vec<i32>>()
+            })
+        });
+        group.bench_function(BenchmarkId::new("sample_into_poly", params.name), |b| {
+            b.iter(|| {
+                let mut samples = vec![0; params.p as usize];
+                gauss.sample_into(&mut samples);
+                samples
+            })
+        });
+        group.bench_function(BenchmarkId::new("load_table", params.name), |b| {
+            b.iter(|| Sampling::from_table_bytes(black_box(params.table)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_zzx(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zzx");
+    group.sample_size(20);
+    for params in PARAMETER_SETS {
+        let es = params.scheme().unwrap();
+        let (pk, _) = es.generate_keys();
+        let (a, b) = (pk.a, pk.p1);
+
+        group.bench_function(BenchmarkId::new("mul", params.name), |bench| {
+            bench.iter(|| {
+                let mut c = ZZX::new();
+                zzx::mul(&mut c, black_box(&a), black_box(&b));
+                c
+            })
+        });
+        group.bench_function(BenchmarkId::new("mulmod", params.name), |bench| {
+            bench.iter(|| zzx::mulmod(black_box(&a), black_box(&b), es.f()))
+        });
+        group.bench_function(BenchmarkId::new("ring_mulmod", params.name), |bench| {
+            bench.iter(|| es.mulmod(black_box(&a), black_box(&b)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_scheme(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scheme");
+    group.sample_size(10);
+    for params in PARAMETER_SETS {
+        let es = params.scheme().unwrap();
+        let (pk, sk) = es.generate_keys();
+
+        let msg: Vec<i32> = (0..params.p).map(|i| i % 2).collect();
+        let mut m = ZZX::new();
+        es.encode(&mut m, &msg);
+        let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+        es.encryption(&mut c1, &mut c2, &pk.a, &pk.p1, &m);
+
+        group.bench_function(BenchmarkId::new("key_generation", params.name), |b| {
+            b.iter(|| {
+                let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+                es.key_generation(black_box(&pk.a), &mut r2, &mut p1);
+                (r2, p1)
+            })
+        });
+        group.bench_function(BenchmarkId::new("encryption", params.name), |b| {
+            b.iter(|| {
+                let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+                es.encryption(&mut c1, &mut c2, &pk.a, &pk.p1, black_box(&m));
+                (c1, c2)
+            })
+        });
+        group.bench_function(BenchmarkId::new("decryption", params.name), |b| {
+            b.iter(|| {
+                let mut m = ZZX::new();
+                es.decryption(&mut m, black_box(&c1), black_box(&c2), &sk.r2);
+                m
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sampling, bench_zzx, bench_scheme);
+criterion_main!(benches);
@@ -0,0 +1,355 @@
+use std::{cell::RefCell, marker::PhantomData};
+
+use rand::RngCore;
+
+// AES-256 CTR-DRBG as in the `rng.c` of the NIST PQC submission package (NIST SP
+// 800-90A without derivation function, prediction resistance or reseeding), so
+// that a scheme seeded with `randombytes_init` produces bit-exact known-answer
+// tests.
+//
+// As in `rng.c`, the generator state is global, here per thread: once a thread
+// calls `randombytes_init`, every random draw of the crate on that thread (keys,
+// errors, messages, `randombits_*`, `RandomBits`) comes from the DRBG, until
+// `randombytes_reset`. Threads that never call it, such as the workers of
+// `failure_experiment`, use the operating system generator. `randombytes_seeded`
+// seeds the thread only for the lifetime of the returned guard, which is what
+// callers that reproduce a record and then go on drawing keys should use.
+//
+// The AES implementation uses table lookups and is not constant time, which is
+// fine for reproducing test vectors but not for production keys.
+
+pub const SEED_BYTES: usize = 48;
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const ROUNDS: usize = 14;
+
+/// AES-256 encryption of single blocks (FIPS 197)
+#[derive(Clone)]
+struct Aes256 {
+    round_keys: [[u8; 16]; ROUNDS + 1],
+}
+
+impl Aes256 {
+    fn new(key: &[u8; 32]) -> Self {
+        // key schedule, as 4-byte words
+        let mut w = [[0u8; 4]; 4 * (ROUNDS + 1)];
+        for (i, word) in key.chunks(4).enumerate() {
+            w[i].copy_from_slice(word);
+        }
+        let mut rcon = 1u8;
+        for i in 8..w.len() {
+            let mut t = w[i - 1];
+            if i % 8 == 0 {
+                t = [
+                    SBOX[t[1] as usize],
+                    SBOX[t[2] as usize],
+                    SBOX[t[3] as usize],
+                    SBOX[t[0] as usize],
+                ];
+                t[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if i % 8 == 4 {
+                t = t.map(|b| SBOX[b as usize]);
+            }
+            for j in 0..4 {
+                w[i][j] = w[i - 8][j] ^ t[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; ROUNDS + 1];
+        for (r, round_key) in round_keys.iter_mut().enumerate() {
+            for c in 0..4 {
+                round_key[4 * c..4 * c + 4].copy_from_slice(&w[4 * r + c]);
+            }
+        }
+        Self { round_keys }
+    }
+
+    /// The state is column-major: byte 4c + r is row r of column c
+    fn encrypt_block(&self, block: &[u8; 16]) -> [u8; 16] {
+        let mut s = *block;
+        add_round_key(&mut s, &self.round_keys[0]);
+        for round in 1..=ROUNDS {
+            for b in s.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            // shift rows: row r rotates left by r columns
+            let t = s;
+            for c in 0..4 {
+                for r in 1..4 {
+                    s[4 * c + r] = t[4 * ((c + r) % 4) + r];
+                }
+            }
+            if round != ROUNDS {
+                for c in 0..4 {
+                    let col = [s[4 * c], s[4 * c + 1], s[4 * c + 2], s[4 * c + 3]];
+                    let all = col[0] ^ col[1] ^ col[2] ^ col[3];
+                    for r in 0..4 {
+                        s[4 * c + r] ^= all ^ xtime(col[r] ^ col[(r + 1) % 4]);
+                    }
+                }
+            }
+            add_round_key(&mut s, &self.round_keys[round]);
+        }
+        s
+    }
+}
+
+/// Multiplication by x in GF(2^8)
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+fn add_round_key(s: &mut [u8; 16], round_key: &[u8; 16]) {
+    for (b, k) in s.iter_mut().zip(round_key.iter()) {
+        *b ^= k;
+    }
+}
+
+/// AES-256 CTR-DRBG with the state and update function of NIST's `rng.c`
+#[derive(Clone)]
+pub struct CtrDrbg {
+    key: [u8; 32],
+    v: [u8; 16],
+    reseed_counter: u64,
+}
+
+impl CtrDrbg {
+    /// Equivalent to `randombytes_init(entropy_input, personalization_string, 256)`
+    pub fn new(
+        entropy_input: &[u8; SEED_BYTES],
+        personalization_string: Option<&[u8; SEED_BYTES]>,
+    ) -> Self {
+        let mut seed_material = *entropy_input;
+        if let Some(personalization) = personalization_string {
+            for (s, p) in seed_material.iter_mut().zip(personalization.iter()) {
+                *s ^= p;
+            }
+        }
+
+        let mut drbg = Self {
+            key: [0; 32],
+            v: [0; 16],
+            reseed_counter: 0,
+        };
+        drbg.update(Some(&seed_material));
+        drbg.reseed_counter = 1;
+        drbg
+    }
+
+    /// Equivalent to `randombytes(x, x.len())`
+    pub fn fill(&mut self, x: &mut [u8]) {
+        let aes = Aes256::new(&self.key);
+        for chunk in x.chunks_mut(16) {
+            self.increment_v();
+            let block = aes.encrypt_block(&self.v);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.update(None);
+        self.reseed_counter += 1;
+    }
+
+    /// Number of `fill` calls since seeding, plus one
+    pub fn reseed_counter(&self) -> u64 {
+        self.reseed_counter
+    }
+
+    fn update(&mut self, provided_data: Option<&[u8; SEED_BYTES]>) {
+        let aes = Aes256::new(&self.key);
+        let mut temp = [0u8; SEED_BYTES];
+        for block in temp.chunks_mut(16) {
+            self.increment_v();
+            block.copy_from_slice(&aes.encrypt_block(&self.v));
+        }
+        if let Some(data) = provided_data {
+            for (t, d) in temp.iter_mut().zip(data.iter()) {
+                *t ^= d;
+            }
+        }
+        self.key.copy_from_slice(&temp[..32]);
+        self.v.copy_from_slice(&temp[32..]);
+    }
+
+    /// V + 1 as a big endian integer
+    fn increment_v(&mut self) {
+        for b in self.v.iter_mut().rev() {
+            let (sum, carry) = b.overflowing_add(1);
+            *b = sum;
+            if !carry {
+                break;
+            }
+        }
+    }
+}
+
+thread_local! {
+    static DRBG: RefCell<Option<CtrDrbg>> = const { RefCell::new(None) };
+}
+
+/// Seeds the DRBG of the current thread, from which all later random draws on it come
+pub fn randombytes_init(
+    entropy_input: &[u8; SEED_BYTES],
+    personalization_string: Option<&[u8; SEED_BYTES]>,
+) {
+    let drbg = CtrDrbg::new(entropy_input, personalization_string);
+    DRBG.with(|state| *state.borrow_mut() = Some(drbg));
+}
+
+/// Returns the current thread to the operating system generator
+pub fn randombytes_reset() {
+    DRBG.with(|state| *state.borrow_mut() = None);
+}
+
+/// Seeds the DRBG of the current thread until the returned guard is dropped,
+/// which restores the generator the thread had before
+pub fn randombytes_seeded(
+    entropy_input: &[u8; SEED_BYTES],
+    personalization_string: Option<&[u8; SEED_BYTES]>,
+) -> SeededGuard {
+    let drbg = CtrDrbg::new(entropy_input, personalization_string);
+    SeededGuard {
+        previous: DRBG.with(|state| state.borrow_mut().replace(drbg)),
+        _thread: PhantomData,
+    }
+}
+
+/// Restores the previous generator of the thread on drop, see `randombytes_seeded`
+#[must_use = "the thread is only seeded while the guard is alive"]
+pub struct SeededGuard {
+    previous: Option<CtrDrbg>,
+    // the state is per thread, so the guard must stay on the thread it seeded
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for SeededGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        DRBG.with(|state| *state.borrow_mut() = previous);
+    }
+}
+
+/// Fills x from the DRBG of the current thread if it was seeded, and from the
+/// operating system generator otherwise
+pub fn randombytes(x: &mut [u8]) {
+    DRBG.with(|state| match state.borrow_mut().as_mut() {
+        Some(drbg) => drbg.fill(x),
+        None => rand::rng().fill_bytes(x),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pke::{Encrypt, KeyGen},
+        util::test_util::*,
+    };
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_aes256() {
+        // FIPS 197, appendix C.3
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let plaintext: [u8; 16] = std::array::from_fn(|i| (i as u8) * 0x11);
+        let ciphertext = Aes256::new(&key).encrypt_block(&plaintext);
+        assert_eq!(hex(&ciphertext), "8ea2b7ca516745bfeafc49904b496089");
+    }
+
+    #[test]
+    fn test_ctr_drbg() {
+        // first seed of the PQCgenKAT_kem known-answer files
+        let entropy_input: [u8; SEED_BYTES] = std::array::from_fn(|i| i as u8);
+        let mut drbg = CtrDrbg::new(&entropy_input, None);
+        let mut seed = [0u8; SEED_BYTES];
+        drbg.fill(&mut seed);
+        assert_eq!(
+            hex(&seed),
+            "061550234d158c5ec95595fe04ef7a25767f2e24cc2bc479d09d86dc9abcfde7\
+             056a8c266f9ef97ed08541dbd2e1ffa1"
+        );
+        assert_eq!(drbg.reseed_counter(), 2);
+
+        // the thread generator reproduces it while seeded
+        let _seeded = randombytes_seeded(&entropy_input, None);
+        let mut again = [0u8; SEED_BYTES];
+        randombytes(&mut again);
+        assert_eq!(again, seed);
+    }
+
+    #[test]
+    fn test_reproducible_encryption() {
+        let es = scheme();
+        let seed = [7u8; SEED_BYTES];
+        let run = || {
+            randombytes_init(&seed, None);
+            let (pk, sk) = es.generate_keys();
+            let msg = random_message();
+            let ct = es.encrypt(&pk, &msg).unwrap();
+            (pk, sk, msg, ct)
+        };
+        assert_eq!(run(), run());
+
+        let first = run();
+        randombytes_init(&seed, Some(&[1; SEED_BYTES]));
+        assert_ne!(es.generate_keys().0, first.0);
+        randombytes_reset();
+    }
+
+    #[test]
+    fn test_seeded_guard() {
+        let seed = [3u8; SEED_BYTES];
+        let draw = || {
+            let mut x = [0u8; SEED_BYTES];
+            randombytes(&mut x);
+            x
+        };
+        let fill = |drbg: &mut CtrDrbg| {
+            let mut x = [0u8; SEED_BYTES];
+            drbg.fill(&mut x);
+            x
+        };
+        let seeded = {
+            let _guard = randombytes_seeded(&seed, None);
+            draw()
+        };
+        assert_eq!(seeded, fill(&mut CtrDrbg::new(&seed, None)));
+        // the operating system generator is back
+        assert_ne!(draw(), seeded);
+        assert_ne!(draw(), draw());
+
+        // guards nest, restoring the outer DRBG mid-stream
+        randombytes_init(&seed, None);
+        let first = draw();
+        {
+            let _guard = randombytes_seeded(&[4; SEED_BYTES], None);
+            draw();
+        }
+        let mut expected = CtrDrbg::new(&seed, None);
+        assert_eq!(first, fill(&mut expected));
+        assert_eq!(draw(), fill(&mut expected));
+
+        randombytes_reset();
+        assert_ne!(draw(), fill(&mut expected));
+    }
+}
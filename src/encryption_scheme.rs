@@ -1,41 +1,97 @@
 use rug::{Float, Integer};
+use sha3::{Digest, Sha3_256};
 
 use crate::{
-    sampling::Sampling,
-    util::zzx::{mulmod, ZZX},
+    error::{Error, Result},
+    pke::{Decrypt, Encrypt, Kem, KeyGen, SHARED_SECRET_BYTES},
+    ring::RingModulus,
+    sampling::{sample_binary, sample_ternary, sample_uniform_mod_q, Sampling, SecretDistribution},
+    util::{randombits_i64, zzx::ZZX, RandomBits},
+    validation::Validation,
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct RlwePublicKey {
+    pub a: ZZX,
+    pub p1: ZZX,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RlweSecretKey {
+    pub r2: ZZX,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RlweCiphertext {
+    pub c1: ZZX,
+    pub c2: ZZX,
+}
+
 #[derive(Debug, Clone)]
 pub struct EncryptionScheme {
     /* Ring parameters */
-    p: i32,
-    q: i32,
+    pub(crate) p: i32,
+    pub(crate) q: i32,
     f: ZZX,
+    modulus: RingModulus,
 
     /* Knuth-Yao discrete Gaussian sampler parameters */
-    tailcut: f32,
+    pub(crate) tailcut: f32,
     sigma: Float,
     center: Float,
 
     gauss: Sampling,
+    secret: SecretDistribution,
+
+    /* Treatment of out of range inputs at the API boundaries */
+    validation: Validation,
 }
 
 impl EncryptionScheme {
-    fn poly_sampling(&self, a: &mut ZZX) {
-        let bound = (self.tailcut * self.sigma.clone().to_f32()).round() as i32;
-        let center = self.center.to_f32().round() as i32;
+    pub(crate) fn poly_sampling(&self, a: &mut ZZX) {
+        self.poly_sampling_with(a, &mut RandomBits::new());
+    }
+
+    /// `poly_sampling` drawing from a random stream shared by several polynomials
+    pub(crate) fn poly_sampling_with(&self, a: &mut ZZX, bits: &mut RandomBits) {
+        let mut samples = vec![0; self.p as usize];
+        self.gauss.sample_into_with(&mut samples, bits);
 
         a.set_length(self.p as usize);
-        for i in 0..self.p as usize {
-            let mut sample = self.gauss.knuth_yao();
-            while (sample >= (center + bound)) || (sample <= (center - bound)) {
-                sample = self.gauss.knuth_yao();
-            }
+        for (i, sample) in samples.into_iter().enumerate() {
+            a.set_coeff(i, Some(sample));
+        }
+    }
+
+    /// A secret drawn from `secret_distribution`
+    pub(crate) fn secret_sampling_with(&self, a: &mut ZZX, bits: &mut RandomBits) {
+        let mut samples = vec![0; self.p as usize];
+        match self.secret {
+            SecretDistribution::Gaussian => self.gauss.sample_into_with(&mut samples, bits),
+            SecretDistribution::Ternary(weight) => sample_ternary(&mut samples, weight, bits),
+            SecretDistribution::Binary => sample_binary(&mut samples, bits),
+        }
+
+        a.set_length(self.p as usize);
+        for (i, sample) in samples.into_iter().enumerate() {
             a.set_coeff(i, Some(sample));
         }
     }
 
-    fn _mod(&self, a: &mut ZZX) {
+    /// a with coefficients uniform in Z_q
+    pub(crate) fn uniform_poly(&self) -> ZZX {
+        let mut samples = vec![0; self.p as usize];
+        sample_uniform_mod_q(&mut samples, self.q, &mut RandomBits::new());
+        ZZX::new_with_vec(samples)
+    }
+
+    pub(crate) fn sample_bound(&self) -> i64 {
+        let bound = (self.tailcut * self.sigma.clone().to_f32()).round() as i64;
+        // samples are rejected unless |sample - center| < bound
+        bound - 1 + self.center.to_f32().round().abs() as i64
+    }
+
+    pub(crate) fn _mod(&self, a: &mut ZZX) {
         for i in 0..self.p as usize {
             a.set_coeff(i, Some(_mod(a.coeff(i).clone(), self.q.into())));
         }
@@ -43,23 +99,154 @@ impl EncryptionScheme {
 }
 
 impl EncryptionScheme {
-    pub fn new(p: i32, q: i32, preicsion: u32, tailcut: f32, sigma: Float, center: Float) -> Self {
-        let mut f = ZZX::new();
-        f.set_length(p as usize + 1);
-        f.set_coeff(p as usize, Some(1));
-        f.set_coeff(0, Some(1));
-
-        let gauss = Sampling::new(preicsion, tailcut, sigma.clone(), center.clone());
-
-        Self {
+    pub fn new(p: i32, q: i32, precision: u32, tailcut: f32, sigma: Float, center: Float) -> Self {
+        Self::new_with_modulus(
             p,
             q,
-            f,
+            precision,
             tailcut,
             sigma,
             center,
+            RingModulus::Cyclotomic,
+        )
+    }
+
+    pub fn new_with_modulus(
+        p: i32,
+        q: i32,
+        precision: u32,
+        tailcut: f32,
+        sigma: Float,
+        center: Float,
+        modulus: RingModulus,
+    ) -> Self {
+        Self::try_new_with_modulus(p, q, precision, tailcut, sigma, center, modulus)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// `new_with_modulus`, or an error if the ring or the sampler parameters are unusable
+    pub fn try_new_with_modulus(
+        p: i32,
+        q: i32,
+        precision: u32,
+        tailcut: f32,
+        sigma: Float,
+        center: Float,
+        modulus: RingModulus,
+    ) -> Result<Self> {
+        let gauss = Sampling::try_new(precision, tailcut, sigma, center)?;
+        Self::try_with_sampler(p, q, gauss, modulus)
+    }
+
+    /// A scheme using an existing sampler, such as one loaded from a precomputed table
+    pub fn try_with_sampler(p: i32, q: i32, gauss: Sampling, modulus: RingModulus) -> Result<Self> {
+        if p < 1 || q < 2 {
+            return Err(Error::InvalidModulus);
+        }
+
+        let f = modulus.polynomial(p as usize);
+
+        Ok(Self {
+            p,
+            q,
+            f,
+            modulus,
+            tailcut: gauss.tailcut(),
+            sigma: gauss.sigma().clone(),
+            center: gauss.center().clone(),
             gauss,
+            secret: SecretDistribution::default(),
+            validation: Validation::default(),
+        })
+    }
+
+    /// Sets how keys, messages and ciphertexts given to the fallible APIs are validated
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn validation(&self) -> Validation {
+        self.validation
+    }
+
+    /// Sets the distribution of the secret r2 of `key_generation`
+    pub fn with_secret_distribution(self, secret: SecretDistribution) -> Self {
+        self.try_with_secret_distribution(secret)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// `with_secret_distribution`, or an error if a ternary weight exceeds p
+    pub fn try_with_secret_distribution(mut self, secret: SecretDistribution) -> Result<Self> {
+        if let SecretDistribution::Ternary(weight) = secret {
+            if weight > self.p as usize {
+                return Err(Error::InvalidSampler(
+                    "ternary weight exceeds the number of coefficients",
+                ));
+            }
+        }
+        self.secret = secret;
+        Ok(self)
+    }
+
+    pub fn secret_distribution(&self) -> SecretDistribution {
+        self.secret
+    }
+
+    pub fn modulus(&self) -> RingModulus {
+        self.modulus
+    }
+
+    /// the polynomial f of the ring Z_q[x]/(f)
+    pub fn f(&self) -> &ZZX {
+        &self.f
+    }
+
+    /// a * b % f, for a and b of degree < p
+    pub fn mulmod(&self, a: &ZZX, b: &ZZX) -> ZZX {
+        self.modulus.reduce(&(a * b), self.p as usize)
+    }
+
+    /// `mulmod`, or an error if a or b has degree >= p
+    pub fn try_mulmod(&self, a: &ZZX, b: &ZZX) -> Result<ZZX> {
+        for c in [a, b] {
+            self.check_degree(c)?;
         }
+        Ok(self.mulmod(a, b))
+    }
+
+    /// Bound on the coefficients of the secret
+    pub(crate) fn secret_bound(&self) -> i64 {
+        match self.secret {
+            SecretDistribution::Gaussian => self.sample_bound(),
+            SecretDistribution::Ternary(_) | SecretDistribution::Binary => 1,
+        }
+    }
+
+    /// E[s^2] for a coefficient s of the secret
+    fn secret_second_moment(&self) -> f64 {
+        match self.secret {
+            SecretDistribution::Gaussian => self.sigma.to_f64().powi(2),
+            SecretDistribution::Ternary(weight) => weight as f64 / self.p as f64,
+            SecretDistribution::Binary => 0.5,
+        }
+    }
+
+    /// Worst case bound on the coefficients of the decryption noise e2 * r2 + r1 * e1 + e3.
+    /// Decryption cannot fail while it is below q / 4.
+    pub fn noise_bound(&self) -> Integer {
+        let b = Integer::from(self.sample_bound());
+        let terms = Integer::from(self.modulus.expansion_factor()) * self.p;
+        terms.clone() * &b * self.secret_bound() + terms * b.clone() * &b + b
+    }
+
+    /// Standard deviation of a coefficient of the decryption noise, assuming independent
+    /// coefficients of standard deviation sigma (central limit heuristic)
+    pub fn noise_std_dev(&self) -> f64 {
+        let sigma = self.sigma.to_f64();
+        let terms = (self.modulus.expansion_factor() * self.p as u32) as f64;
+        let e2_r2 = terms * sigma.powi(2) * self.secret_second_moment();
+        (e2_r2 + terms * sigma.powi(4) + sigma.powi(2)).sqrt()
     }
 
     pub fn key_generation(&self, a: &ZZX, r2: &mut ZZX, p1: &mut ZZX) {
@@ -69,10 +256,11 @@ impl EncryptionScheme {
         c.set_length(self.p as usize);
         r1.set_length(self.p as usize);
 
-        self.poly_sampling(&mut r1);
-        self.poly_sampling(r2);
+        let mut bits = RandomBits::new();
+        self.poly_sampling_with(&mut r1, &mut bits);
+        self.secret_sampling_with(r2, &mut bits);
 
-        c = mulmod(a, r2, &self.f);
+        c = self.mulmod(a, r2);
         *p1 = r1 - c;
 
         self._mod(p1);
@@ -100,6 +288,21 @@ impl EncryptionScheme {
         }
     }
 
+    /// `encode`, or an error if a is not a valid message (see `validate_message`)
+    pub fn try_encode(&self, aprime: &mut ZZX, a: &[i32]) -> Result<()> {
+        let a = self.validate_message(a)?;
+        self.encode(aprime, &a);
+        Ok(())
+    }
+
+    /// `decode`, or an error if a cannot hold p bits or aprime has degree >= p
+    pub fn try_decode(&self, a: &mut [i32], aprime: &ZZX) -> Result<()> {
+        self.check_message_len(a.len())?;
+        self.check_degree(aprime)?;
+        self.decode(a, aprime);
+        Ok(())
+    }
+
     pub fn encryption(&self, c1: &mut ZZX, c2: &mut ZZX, a: &ZZX, p1: &ZZX, m: &ZZX) {
         c1.set_length(self.p as usize);
         c2.set_length(self.p as usize);
@@ -114,14 +317,15 @@ impl EncryptionScheme {
         let mut e2 = ZZX::new();
         let mut e3 = ZZX::new();
 
-        self.poly_sampling(&mut e1);
-        self.poly_sampling(&mut e2);
-        self.poly_sampling(&mut e3);
+        let mut bits = RandomBits::new();
+        self.poly_sampling_with(&mut e1, &mut bits);
+        self.poly_sampling_with(&mut e2, &mut bits);
+        self.poly_sampling_with(&mut e3, &mut bits);
 
         add = e3 + m;
-        mult = mulmod(p1, &e1, &self.f);
+        mult = self.mulmod(p1, &e1);
         *c2 = &mult + &add;
-        mult = mulmod(a, &e1, &self.f);
+        mult = self.mulmod(a, &e1);
         *c1 = mult + e2;
 
         self._mod(c1);
@@ -134,13 +338,122 @@ impl EncryptionScheme {
 
         m.set_length(self.p as usize);
 
-        mult = mulmod(c1, r2, &self.f);
-        *m = mult - c2;
+        // c1 * r2 + c2 = m + e2 * r2 + r1 * e1 + e3
+        mult = self.mulmod(c1, r2);
+        *m = mult + c2;
 
         self._mod(m);
     }
+
+    /// `decryption`, or an error if c1, c2 or r2 is not valid (see `validate_poly`
+    /// and `validate_secret_key`)
+    pub fn try_decryption(&self, m: &mut ZZX, c1: &ZZX, c2: &ZZX, r2: &ZZX) -> Result<()> {
+        let c1 = self.validate_poly(c1)?;
+        let c2 = self.validate_poly(c2)?;
+        let r2 = self.validate_short_poly(r2)?;
+        self.decryption(m, &c1, &c2, &r2);
+        Ok(())
+    }
+}
+
+impl KeyGen for EncryptionScheme {
+    type PublicKey = RlwePublicKey;
+    type SecretKey = RlweSecretKey;
+
+    fn generate_keys(&self) -> (RlwePublicKey, RlweSecretKey) {
+        let a = self.uniform_poly();
+        let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+        self.key_generation(&a, &mut r2, &mut p1);
+
+        (RlwePublicKey { a, p1 }, RlweSecretKey { r2 })
+    }
+}
+
+impl Encrypt for EncryptionScheme {
+    type Ciphertext = RlweCiphertext;
+
+    fn message_len(&self) -> usize {
+        self.p as usize
+    }
+
+    fn encrypt(&self, pk: &RlwePublicKey, msg: &[i32]) -> Result<RlweCiphertext> {
+        let mut mprime = ZZX::new();
+        self.try_encode(&mut mprime, msg)?;
+        let pk = self.validate_public_key(pk)?;
+
+        let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+        self.encryption(&mut c1, &mut c2, &pk.a, &pk.p1, &mprime);
+
+        Ok(RlweCiphertext { c1, c2 })
+    }
+}
+
+impl Decrypt for EncryptionScheme {
+    fn decrypt(&self, sk: &RlweSecretKey, ct: &RlweCiphertext) -> Result<Vec<i32>> {
+        let sk = self.validate_secret_key(sk)?;
+        let ct = self.validate_ciphertext(ct)?;
+
+        let mut mprime = ZZX::new();
+        self.decryption(&mut mprime, &ct.c1, &ct.c2, &sk.r2);
+
+        let mut msg = vec![0; self.p as usize];
+        self.decode(&mut msg, &mprime);
+        Ok(msg)
+    }
+}
+
+/// KEM encrypting p random bits and hashing them into the shared secret. It is only
+/// as secure as the encryption scheme (IND-CPA), with no re-encryption check.
+impl Kem for EncryptionScheme {
+    type Encapsulation = RlweCiphertext;
+
+    fn encapsulate(
+        &self,
+        pk: &RlwePublicKey,
+    ) -> Result<(RlweCiphertext, [u8; SHARED_SECRET_BYTES])> {
+        let msg: Vec<i32> = (0..self.p).map(|_| randombits_i64(1) as i32).collect();
+        Ok((self.encrypt(pk, &msg)?, shared_secret(&msg)))
+    }
+
+    fn decapsulate(
+        &self,
+        sk: &RlweSecretKey,
+        ct: &RlweCiphertext,
+    ) -> Result<[u8; SHARED_SECRET_BYTES]> {
+        Ok(shared_secret(&self.decrypt(sk, ct)?))
+    }
+}
+
+fn shared_secret(msg: &[i32]) -> [u8; SHARED_SECRET_BYTES] {
+    let bytes: Vec<u8> = msg
+        .chunks(8)
+        .map(|bits| bits.iter().rev().fold(0, |byte, &b| (byte << 1) | b as u8))
+        .collect();
+    Sha3_256::digest(bytes).into()
 }
 
 fn _mod(i: Integer, n: Integer) -> Integer {
     (i % n.clone() + n.clone()) % n
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::*;
+
+    #[test]
+    fn test_encryption() {
+        let es = scheme();
+        let a = uniform_poly();
+        let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+        es.key_generation(&a, &mut r2, &mut p1);
+
+        check_round_trips(&es, &a, &p1, 10, |c1, c2| {
+            let mut moriginal = ZZX::new();
+            es.decryption(&mut moriginal, c1, c2, &r2);
+            let mut mdecoded = vec![0; P as usize];
+            es.decode(&mut mdecoded, &moriginal);
+            mdecoded
+        });
+    }
+}
@@ -0,0 +1,119 @@
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Digest, Sha3_256, Shake256,
+};
+
+use crate::{
+    encryption_scheme::{EncryptionScheme, RlweCiphertext, RlwePublicKey, RlweSecretKey},
+    error::{Error, Result},
+    pke::{Kem, SHARED_SECRET_BYTES},
+    serialization::encode_poly,
+    util::ct_eq,
+};
+
+// Hybrid encryption of arbitrary data: the scheme's KEM carries a fresh shared
+// secret, from which SHAKE256 derives an encryption and a MAC key. The body is
+// XORed with the SHAKE256 keystream of the encryption key, and the tag is
+// SHA3-256(mac key | c1 | c2 | body) (encrypt-then-MAC).
+//
+// Every envelope uses a new shared secret, so no nonce is needed. With small p,
+// the KEM secret has only p bits of entropy.
+
+pub const TAG_BYTES: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub kem: RlweCiphertext,
+    pub body: Vec<u8>,
+    pub tag: [u8; TAG_BYTES],
+}
+
+impl EncryptionScheme {
+    /// (encryption key, MAC key) derived from the KEM shared secret
+    fn envelope_keys(&self, secret: &[u8; SHARED_SECRET_BYTES]) -> ([u8; 32], [u8; 32]) {
+        let mut hasher = Shake256::default();
+        hasher.update(b"rlwe-envelope");
+        hasher.update(secret);
+        let mut reader = hasher.finalize_xof();
+
+        let (mut enc_key, mut mac_key) = ([0u8; 32], [0u8; 32]);
+        reader.read(&mut enc_key);
+        reader.read(&mut mac_key);
+        (enc_key, mac_key)
+    }
+
+    fn apply_keystream(&self, enc_key: &[u8; 32], data: &mut [u8]) {
+        let mut hasher = Shake256::default();
+        hasher.update(enc_key);
+        let mut reader = hasher.finalize_xof();
+
+        let mut keystream = vec![0u8; data.len()];
+        reader.read(&mut keystream);
+        for (d, k) in data.iter_mut().zip(keystream) {
+            *d ^= k;
+        }
+    }
+
+    fn envelope_tag(
+        &self,
+        mac_key: &[u8; 32],
+        kem: &RlweCiphertext,
+        body: &[u8],
+    ) -> Result<[u8; TAG_BYTES]> {
+        let mut transcript = mac_key.to_vec();
+        encode_poly(&mut transcript, &kem.c1, self.p as usize)?;
+        encode_poly(&mut transcript, &kem.c2, self.p as usize)?;
+        transcript.extend_from_slice(body);
+        Ok(Sha3_256::digest(transcript).into())
+    }
+
+    /// Encrypts `plaintext` of any length under pk
+    pub fn seal(&self, pk: &RlwePublicKey, plaintext: &[u8]) -> Result<Envelope> {
+        let (kem, secret) = self.encapsulate(pk)?;
+        let (enc_key, mac_key) = self.envelope_keys(&secret);
+
+        let mut body = plaintext.to_vec();
+        self.apply_keystream(&enc_key, &mut body);
+        let tag = self.envelope_tag(&mac_key, &kem, &body)?;
+
+        Ok(Envelope { kem, body, tag })
+    }
+
+    /// Decrypts an envelope, or returns `Error::Authentication` if it was
+    /// modified or sealed for another key
+    pub fn open(&self, sk: &RlweSecretKey, envelope: &Envelope) -> Result<Vec<u8>> {
+        let secret = self.decapsulate(sk, &envelope.kem)?;
+        let (enc_key, mac_key) = self.envelope_keys(&secret);
+
+        let tag = self.envelope_tag(&mac_key, &envelope.kem, &envelope.body)?;
+        if !ct_eq(&tag, &envelope.tag) {
+            return Err(Error::Authentication);
+        }
+
+        let mut plaintext = envelope.body.clone();
+        self.apply_keystream(&enc_key, &mut plaintext);
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pke::KeyGen, util::test_util::*};
+
+    #[test]
+    fn test_seal_open() {
+        let es = scheme();
+        let (pk, sk) = es.generate_keys();
+
+        for plaintext in [&b""[..], &b"attack at dawn"[..], &[7u8; 1000][..]] {
+            let envelope = es.seal(&pk, plaintext).unwrap();
+            assert_eq!(envelope.body.len(), plaintext.len());
+            assert_eq!(es.open(&sk, &envelope), Ok(plaintext.to_vec()));
+        }
+
+        let mut envelope = es.seal(&pk, b"attack at dawn").unwrap();
+        envelope.body[0] ^= 1;
+        assert_eq!(es.open(&sk, &envelope), Err(Error::Authentication));
+    }
+}
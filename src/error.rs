@@ -0,0 +1,118 @@
+use std::fmt;
+
+use rug::Integer;
+
+/// Errors reported by the fallible APIs of the crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A polynomial of degree `found` where the ring needs degree < `bound`
+    WrongDegree {
+        bound: i64,
+        found: i64,
+    },
+    /// A coefficient that is not reduced mod `q`, e.g. produced under another modulus
+    ModulusMismatch {
+        q: Integer,
+        found: Integer,
+    },
+    /// A vector of `found` elements (e.g. part of a ciphertext) where `expected` are needed
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+    /// The ring polynomial is constant or not monic, or q < 2
+    InvalidModulus,
+    DivisionByZero,
+    /// An element with no inverse mod q, e.g. n! when q shares a factor with it
+    NotInvertible,
+    /// The quotient or remainder of a division has non-integer coefficients
+    InexactDivision,
+    /// A message of `found` bits where the scheme encrypts `expected`
+    MessageLength {
+        expected: usize,
+        found: usize,
+    },
+    /// A message bit at `index` that is neither 0 nor 1
+    NonBinaryMessage {
+        index: usize,
+        value: i32,
+    },
+    /// A public or secret key that cannot have been produced by key generation
+    MalformedKey(&'static str),
+    /// Serialized data that cannot be parsed
+    Format(&'static str),
+    /// Serialized data referring to a parameter set this build does not know
+    UnknownParameterSet(String),
+    /// An envelope whose tag does not match, because it was tampered with or
+    /// opened with the wrong key
+    Authentication,
+    /// A number of bits (of randomness, noise or a digit base) outside of min..=max
+    InvalidBits {
+        bits: u32,
+        min: u32,
+        max: u32,
+    },
+    /// Unusable sampler parameters, e.g. of the discrete gaussian or a ternary weight
+    /// above the dimension
+    InvalidSampler(&'static str),
+    /// Key or decryption shares that cannot be combined, e.g. none at all
+    InvalidShares(&'static str),
+    /// Parameters a scheme cannot be instantiated with
+    InvalidParameters(&'static str),
+    /// A threshold `t` of `n` key-holders outside of 1..=n
+    InvalidThreshold {
+        t: usize,
+        n: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WrongDegree { bound, found } => {
+                write!(
+                    f,
+                    "polynomial of degree {} where {} is the maximum",
+                    found,
+                    bound - 1
+                )
+            }
+            Error::ModulusMismatch { q, found } => {
+                write!(f, "coefficient {} is not reduced mod {}", found, q)
+            }
+            Error::WrongLength { expected, found } => {
+                write!(f, "{} elements where {} are expected", found, expected)
+            }
+            Error::InvalidModulus => write!(
+                f,
+                "the ring polynomial must be monic and non-constant, and q at least 2"
+            ),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::NotInvertible => write!(f, "element is not invertible mod q"),
+            Error::InexactDivision => write!(f, "division not defined over ZZ"),
+            Error::MessageLength { expected, found } => {
+                write!(f, "message of {} bits, expected {}", found, expected)
+            }
+            Error::NonBinaryMessage { index, value } => {
+                write!(f, "message bit {} is {}, expected 0 or 1", index, value)
+            }
+            Error::MalformedKey(reason) => write!(f, "malformed key: {}", reason),
+            Error::Format(reason) => write!(f, "malformed data: {}", reason),
+            Error::UnknownParameterSet(name) => write!(f, "unknown parameter set {:?}", name),
+            Error::Authentication => write!(f, "authentication failed"),
+            Error::InvalidBits { bits, min, max } => {
+                write!(f, "{} bits, must be between {} and {}", bits, min, max)
+            }
+            Error::InvalidSampler(reason) => write!(f, "invalid sampler parameters: {}", reason),
+            Error::InvalidShares(reason) => write!(f, "invalid shares: {}", reason),
+            Error::InvalidParameters(reason) => write!(f, "invalid parameters: {}", reason),
+            Error::InvalidThreshold { t, n } => {
+                write!(f, "threshold {} of {}, must be between 1 and {}", t, n, n)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,311 @@
+use std::{collections::BTreeMap, thread};
+
+use rug::{ops::RemRounding, Float, Integer};
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    pke::KeyGen,
+    util::{randombits_i64, zzx::ZZX},
+};
+
+// Monte Carlo estimation of the decryption failure rate. Random messages are
+// encrypted and decrypted, under a fresh key pair every `trials_per_key`
+// messages, recording for every coefficient whether it decoded correctly and
+// its noise m' - encode(m), centered mod q. The noise is kept per coefficient
+// index, as its distribution depends on the index in rings other than x^p + 1.
+//
+// The analytic prediction models a noise coefficient as a gaussian with the
+// standard deviation of `EncryptionScheme::noise_std_dev`, which fails once
+// its magnitude reaches q/4.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureReport {
+    /// Number of encrypted messages
+    pub trials: usize,
+    /// Number of key pairs the messages were encrypted under
+    pub key_pairs: usize,
+    /// Number of messages with at least one wrong bit
+    pub failed_messages: usize,
+    /// Wrong bits at every coefficient index
+    pub coefficient_errors: Vec<usize>,
+    /// Noise at every coefficient index, counted per bin of `bin_width` (keyed by
+    /// the lower end)
+    pub noise_histograms: Vec<BTreeMap<i64, usize>>,
+    pub bin_width: i64,
+    /// Sum and sum of squares of the noise at every coefficient index
+    noise_sums: Vec<f64>,
+    noise_sums_squares: Vec<f64>,
+    /// Analytic probability that a single coefficient fails
+    pub predicted_coefficient_failure: f64,
+}
+
+impl FailureReport {
+    fn new(p: usize, bin_width: i64, predicted_coefficient_failure: f64) -> Self {
+        Self {
+            trials: 0,
+            key_pairs: 0,
+            failed_messages: 0,
+            coefficient_errors: vec![0; p],
+            noise_histograms: vec![BTreeMap::new(); p],
+            bin_width,
+            noise_sums: vec![0.0; p],
+            noise_sums_squares: vec![0.0; p],
+            predicted_coefficient_failure,
+        }
+    }
+
+    fn merge(&mut self, other: FailureReport) {
+        self.trials += other.trials;
+        self.key_pairs += other.key_pairs;
+        self.failed_messages += other.failed_messages;
+        for (total, errors) in self
+            .coefficient_errors
+            .iter_mut()
+            .zip(other.coefficient_errors)
+        {
+            *total += errors;
+        }
+        for (histogram, other) in self.noise_histograms.iter_mut().zip(other.noise_histograms) {
+            for (bin, count) in other {
+                *histogram.entry(bin).or_insert(0) += count;
+            }
+        }
+        for i in 0..self.noise_sums.len() {
+            self.noise_sums[i] += other.noise_sums[i];
+            self.noise_sums_squares[i] += other.noise_sums_squares[i];
+        }
+    }
+
+    /// Number of decrypted coefficients
+    pub fn coefficients(&self) -> usize {
+        self.trials * self.coefficient_errors.len()
+    }
+
+    pub fn failed_coefficients(&self) -> usize {
+        self.coefficient_errors.iter().sum()
+    }
+
+    pub fn message_failure_rate(&self) -> f64 {
+        self.failed_messages as f64 / self.trials as f64
+    }
+
+    pub fn coefficient_failure_rate(&self) -> f64 {
+        self.failed_coefficients() as f64 / self.coefficients() as f64
+    }
+
+    /// Wilson score interval of the message failure rate, for a normal quantile z
+    /// (1.96 for 95%)
+    pub fn message_failure_interval(&self, z: f64) -> (f64, f64) {
+        wilson_interval(self.failed_messages, self.trials, z)
+    }
+
+    pub fn coefficient_failure_interval(&self, z: f64) -> (f64, f64) {
+        wilson_interval(self.failed_coefficients(), self.coefficients(), z)
+    }
+
+    /// Analytic message failure rate, assuming independent coefficients
+    pub fn predicted_message_failure(&self) -> f64 {
+        let p = self.coefficient_errors.len() as i32;
+        1.0 - (1.0 - self.predicted_coefficient_failure).powi(p)
+    }
+
+    /// Empirical standard deviation of all noise coefficients
+    pub fn noise_std_dev(&self) -> f64 {
+        let n = self.coefficients() as f64;
+        let mean = self.noise_sums.iter().sum::<f64>() / n;
+        let squares = self.noise_sums_squares.iter().sum::<f64>() / n;
+        (squares - mean * mean).max(0.0).sqrt()
+    }
+
+    /// Empirical mean of the noise at coefficient index i
+    pub fn coefficient_noise_mean(&self, i: usize) -> f64 {
+        self.noise_sums[i] / self.trials as f64
+    }
+
+    /// Empirical standard deviation of the noise at coefficient index i
+    pub fn coefficient_noise_std_dev(&self, i: usize) -> f64 {
+        let mean = self.coefficient_noise_mean(i);
+        let squares = self.noise_sums_squares[i] / self.trials as f64;
+        (squares - mean * mean).max(0.0).sqrt()
+    }
+
+    /// Histogram of the noise over all coefficient indices
+    pub fn noise_histogram(&self) -> BTreeMap<i64, usize> {
+        let mut total = BTreeMap::new();
+        for histogram in &self.noise_histograms {
+            for (&bin, count) in histogram {
+                *total.entry(bin).or_insert(0) += count;
+            }
+        }
+        total
+    }
+}
+
+/// Wilson score interval of a binomial proportion
+pub fn wilson_interval(successes: usize, trials: usize, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    // the bounds are exactly 0 and 1 at the extremes, up to rounding
+    let lo = if successes == 0 {
+        0.0
+    } else {
+        (center - half).max(0.0)
+    };
+    let hi = if successes == trials {
+        1.0
+    } else {
+        (center + half).min(1.0)
+    };
+    (lo, hi)
+}
+
+impl EncryptionScheme {
+    /// P(|X| >= q/4) for X gaussian with standard deviation `noise_std_dev`
+    pub fn predicted_coefficient_failure(&self) -> f64 {
+        let precision = 128;
+        let mut x = Float::with_val(precision, self.q) / (4.0 * self.noise_std_dev());
+        x /= Float::with_val(precision, 2).sqrt();
+        x.erfc().to_f64()
+    }
+
+    /// Encrypts and decrypts `trials` random messages, spread over `threads` threads,
+    /// with each thread generating a fresh key pair every `trials_per_key` messages.
+    /// The noise is counted in bins of `bin_width`.
+    pub fn failure_experiment(
+        &self,
+        trials: usize,
+        trials_per_key: usize,
+        threads: usize,
+        bin_width: i64,
+    ) -> FailureReport {
+        assert!(threads > 0, "failure_experiment: no threads");
+        assert!(
+            trials_per_key > 0,
+            "failure_experiment: trials_per_key must be positive"
+        );
+        assert!(
+            bin_width > 0,
+            "failure_experiment: bin_width must be positive"
+        );
+
+        let predicted = self.predicted_coefficient_failure();
+
+        let mut report = FailureReport::new(self.p as usize, bin_width, predicted);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|t| {
+                    let share = trials / threads + (t < trials % threads) as usize;
+                    scope.spawn(move || {
+                        let mut report = FailureReport::new(self.p as usize, bin_width, predicted);
+                        let mut done = 0;
+                        while done < share {
+                            let (pk, sk) = self.generate_keys();
+                            report.key_pairs += 1;
+                            for _ in 0..trials_per_key.min(share - done) {
+                                self.failure_trial(&mut report, &pk.a, &pk.p1, &sk.r2);
+                            }
+                            done += trials_per_key;
+                        }
+                        report
+                    })
+                })
+                .collect();
+
+            for worker in workers {
+                report.merge(worker.join().unwrap());
+            }
+        });
+        report
+    }
+
+    fn failure_trial(&self, report: &mut FailureReport, a: &ZZX, p1: &ZZX, r2: &ZZX) {
+        let p = self.p as usize;
+        let q = Integer::from(self.q);
+
+        let msg: Vec<i32> = (0..p).map(|_| randombits_i64(1) as i32).collect();
+        let mut encoded = ZZX::new();
+        self.encode(&mut encoded, &msg);
+
+        let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+        self.encryption(&mut c1, &mut c2, a, p1, &encoded);
+        let mut mprime = ZZX::new();
+        self.decryption(&mut mprime, &c1, &c2, r2);
+        let mut decoded = vec![0; p];
+        self.decode(&mut decoded, &mprime);
+
+        report.trials += 1;
+        let mut failed = false;
+        for i in 0..p {
+            if decoded[i] != msg[i] {
+                report.coefficient_errors[i] += 1;
+                failed = true;
+            }
+
+            // noise centered into (-q/2, q/2]
+            let mut noise = (mprime.coeff(i) - encoded.coeff(i)).rem_euc(&q);
+            if noise.clone() * 2 > q {
+                noise -= &q;
+            }
+            let noise = noise.to_i64().unwrap();
+
+            let bin = noise.div_euclid(report.bin_width) * report.bin_width;
+            *report.noise_histograms[i].entry(bin).or_insert(0) += 1;
+            report.noise_sums[i] += noise as f64;
+            report.noise_sums_squares[i] += (noise as f64).powi(2);
+        }
+        report.failed_messages += failed as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::*;
+
+    #[test]
+    fn test_wilson_interval() {
+        let (lo, hi) = wilson_interval(0, 100, 1.96);
+        assert_eq!(lo, 0.0);
+        assert!((hi - 0.037).abs() < 1e-3);
+
+        let (lo, hi) = wilson_interval(50, 100, 1.96);
+        assert!((lo - 0.404).abs() < 1e-3);
+        assert!((hi - 0.596).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_failure_experiment() {
+        let es = scheme();
+        let report = es.failure_experiment(200, 20, 3, 10);
+
+        assert_eq!(report.trials, 200);
+        // 67, 67 and 66 trials per thread
+        assert_eq!(report.key_pairs, 4 + 4 + 4);
+        assert_eq!(report.failed_messages, 0);
+        assert_eq!(report.coefficients(), 200 * P as usize);
+        for histogram in &report.noise_histograms {
+            assert_eq!(histogram.values().sum::<usize>(), report.trials);
+        }
+        assert_eq!(
+            report.noise_histogram().values().sum::<usize>(),
+            report.coefficients()
+        );
+        assert!(report.predicted_coefficient_failure < 1e-9);
+
+        // the central limit model should be close to the observed noise, at every
+        // coefficient index too
+        let ratio = report.noise_std_dev() / es.noise_std_dev();
+        assert!(ratio > 0.7 && ratio < 1.3, "ratio = {}", ratio);
+        for i in 0..P as usize {
+            let ratio = report.coefficient_noise_std_dev(i) / es.noise_std_dev();
+            assert!(ratio > 0.5 && ratio < 1.5, "ratio = {} at {}", ratio, i);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use rug::Integer;
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    util::{randombits_i64, zzx::ZZX},
+};
+
+// Key exchange with Peikert's reconciliation (https://eprint.iacr.org/2014/070.pdf).
+//
+// Alice (initiator) sends p1 = r1 - a * r2. Bob (responder) samples s', e', e''
+// and sends u = a * s' + e' together with the cross-rounding of v = p1 * s' + e''.
+// Alice's w = -u * r2 differs from v by r1 * s' + e'' + e' * r2 only, so both
+// sides derive the same bits. Since q is odd, v is first randomly doubled
+// to an element of Z_{2q}.
+
+/// Bob's answer to Alice's p1
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyExchangeResponse {
+    pub u: ZZX,
+    pub hint: Vec<i32>,
+}
+
+/// Alice's side, holding her secret between the two messages
+#[derive(Debug, Clone)]
+pub struct KeyExchangeInitiator<'a> {
+    es: &'a EncryptionScheme,
+    r2: ZZX,
+    p1: ZZX,
+}
+
+/// Bob's side
+#[derive(Debug, Clone)]
+pub struct KeyExchangeResponder<'a> {
+    es: &'a EncryptionScheme,
+    a: ZZX,
+}
+
+impl<'a> KeyExchangeInitiator<'a> {
+    pub fn new(es: &'a EncryptionScheme, a: &ZZX) -> Self {
+        let mut r2 = ZZX::new();
+        let mut p1 = ZZX::new();
+        es.key_generation(a, &mut r2, &mut p1);
+
+        Self { es, r2, p1 }
+    }
+
+    /// p1, to be sent to the responder
+    pub fn message(&self) -> &ZZX {
+        &self.p1
+    }
+
+    /// Consumes the responder's answer and returns the shared key bits. Fails if
+    /// u is not an element of R_q or the hint does not have p bits.
+    pub fn finish(self, response: &KeyExchangeResponse) -> Result<Vec<i32>> {
+        let es = self.es;
+        es.check_reduced(&response.u)?;
+        if response.hint.len() != es.p as usize {
+            return Err(Error::WrongLength {
+                expected: es.p as usize,
+                found: response.hint.len(),
+            });
+        }
+
+        let mut w = -es.mulmod(&response.u, &self.r2);
+        es._mod(&mut w);
+
+        let q = es.q as i64;
+        Ok((0..es.p as usize)
+            .map(|i| {
+                let w_i = w.coeff(i).to_i64().unwrap();
+                reconcile(2 * w_i, response.hint[i], q)
+            })
+            .collect())
+    }
+}
+
+impl<'a> KeyExchangeResponder<'a> {
+    pub fn new(es: &'a EncryptionScheme, a: &ZZX) -> Self {
+        Self { es, a: a.clone() }
+    }
+
+    /// Answers the initiator's p1, returning the response and the shared key bits
+    pub fn respond(self, p1: &ZZX) -> (KeyExchangeResponse, Vec<i32>) {
+        let es = self.es;
+
+        let mut s = ZZX::new();
+        let mut e1 = ZZX::new();
+        let mut e2 = ZZX::new();
+        es.poly_sampling(&mut s);
+        es.poly_sampling(&mut e1);
+        es.poly_sampling(&mut e2);
+
+        let mut u = es.mulmod(&self.a, &s) + e1;
+        es._mod(&mut u);
+
+        let mut v = es.mulmod(p1, &s) + e2;
+        es._mod(&mut v);
+
+        let q = es.q as i64;
+        let mut hint = Vec::with_capacity(es.p as usize);
+        let mut key = Vec::with_capacity(es.p as usize);
+        for i in 0..es.p as usize {
+            let v_i = randomized_doubling(v.coeff(i), q);
+            hint.push(cross_rounding(v_i, q));
+            key.push(modular_rounding(v_i, q));
+        }
+
+        (KeyExchangeResponse { u, hint }, key)
+    }
+}
+
+/// 2v - e mod 2q, where e is -1, 0, 1 with probabilities 1/4, 1/2, 1/4
+fn randomized_doubling(v: Integer, q: i64) -> i64 {
+    let e = randombits_i64(1) - randombits_i64(1);
+    (2 * v.to_i64().unwrap() - e).rem_euclid(2 * q)
+}
+
+/// <v>_2 = floor(4v / 2q) mod 2
+fn cross_rounding(v: i64, q: i64) -> i32 {
+    ((4 * v).div_euclid(2 * q) % 2) as i32
+}
+
+/// round(2v / 2q) mod 2
+fn modular_rounding(v: i64, q: i64) -> i32 {
+    ((2 * v + q).div_euclid(2 * q) % 2) as i32
+}
+
+/// 0 if w is in I_b + E (mod 2q), 1 otherwise, where
+/// I_0 = {0, ..., round(2q/4) - 1}, I_1 = {-floor(2q/4), ..., -1} and E = [-2q/8, 2q/8)
+fn reconcile(w: i64, b: i32, q: i64) -> i32 {
+    let q2 = 2 * q;
+    let e_min = -(q2 / 8); // ceil(-2q/8)
+    let e_max = (q2 + 7) / 8 - 1; // largest integer < 2q/8
+
+    let (lo, hi) = if b == 0 {
+        (e_min, (q2 + 2) / 4 - 1 + e_max)
+    } else {
+        (-(q2 / 4) + e_min, -1 + e_max)
+    };
+
+    if (w - lo).rem_euclid(q2) <= hi - lo {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::*;
+
+    #[test]
+    fn test_reconcile() {
+        let q = 97;
+        for v in 0..2 * q {
+            let b = cross_rounding(v, q);
+            let key = modular_rounding(v, q);
+            // any w with |v - w| < 2q/8 agrees
+            for err in -(2 * q / 8 - 1)..(2 * q / 8) {
+                let w = (v + err).rem_euclid(2 * q);
+                assert_eq!(reconcile(w, b, q), key, "v = {}, w = {}", v, w);
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_exchange() {
+        let es = scheme();
+        let a = uniform_poly();
+
+        for _ in 0..10 {
+            let alice = KeyExchangeInitiator::new(&es, &a);
+            let bob = KeyExchangeResponder::new(&es, &a);
+
+            let (response, bob_key) = bob.respond(alice.message());
+            let alice_key = alice.finish(&response).unwrap();
+
+            assert_eq!(alice_key.len(), P as usize);
+            assert_eq!(alice_key, bob_key);
+        }
+    }
+
+    #[test]
+    fn test_malformed_response() {
+        let es = scheme();
+        let a = uniform_poly();
+        let (response, _) = KeyExchangeResponder::new(&es, &a).respond(&uniform_poly());
+
+        let mut short = response.clone();
+        short.hint.pop();
+        assert_eq!(
+            KeyExchangeInitiator::new(&es, &a).finish(&short),
+            Err(Error::WrongLength {
+                expected: P as usize,
+                found: P as usize - 1
+            })
+        );
+
+        let mut unreduced = response;
+        unreduced.u.set_coeff(0, Some(Q));
+        assert!(matches!(
+            KeyExchangeInitiator::new(&es, &a).finish(&unreduced),
+            Err(Error::ModulusMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,250 @@
+use rug::Integer;
+
+use crate::{
+    encryption_scheme::{EncryptionScheme, RlweCiphertext, RlwePublicKey, RlweSecretKey},
+    error::{Error, Result},
+    util::zzx::ZZX,
+};
+
+/// Base (in bits) of the gadget decomposition used by `rekey`
+pub const REKEY_BASE_BITS: u32 = 4;
+
+/// Largest base (in bits) of the gadget decomposition
+pub const MAX_BASE_BITS: u32 = 31;
+
+/// Encryptions of `2^{w*j} * r2` under another key, for j = 0..l-1
+#[derive(Debug, Clone)]
+pub struct KeySwitchingKey {
+    base_bits: u32,
+    b: Vec<ZZX>,
+    k: Vec<ZZX>,
+}
+
+/// Proxy re-encryption key from Alice to Bob
+pub type ReKey = KeySwitchingKey;
+
+impl KeySwitchingKey {
+    pub fn base_bits(&self) -> u32 {
+        self.base_bits
+    }
+
+    /// number of digits of the gadget decomposition
+    pub fn len(&self) -> usize {
+        self.b.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.b.is_empty()
+    }
+}
+
+impl EncryptionScheme {
+    /// Number of base 2^{base_bits} digits needed to represent an element of [0, q)
+    fn gadget_length(&self, base_bits: u32) -> usize {
+        let bits = Integer::from(self.q - 1).significant_bits();
+        bits.div_ceil(base_bits) as usize
+    }
+
+    /// Splits every coefficient of a (reduced mod q) into its base 2^{base_bits} digits,
+    /// so that a = sum_j 2^{base_bits*j} * digits[j]
+    fn gadget_decompose(&self, a: &ZZX, base_bits: u32) -> Vec<ZZX> {
+        let mask = Integer::from((1u64 << base_bits) - 1);
+        let length = self.gadget_length(base_bits);
+
+        let mut digits = vec![ZZX::new(); length];
+        for (j, digit) in digits.iter_mut().enumerate() {
+            digit.set_length(self.p as usize);
+            for i in 0..self.p as usize {
+                let shifted: Integer = a.coeff(i) >> (base_bits * j as u32);
+                digit[i] = shifted & mask.clone();
+            }
+            digit.normalize();
+        }
+        digits
+    }
+
+    /// Builds the key which switches ciphertexts under `r2` to ciphertexts under
+    /// the secret key matching `p1_new`, or an error if base_bits is not between
+    /// 1 and `MAX_BASE_BITS`
+    pub fn key_switching_key(
+        &self,
+        a: &ZZX,
+        r2: &ZZX,
+        p1_new: &ZZX,
+        base_bits: u32,
+    ) -> Result<KeySwitchingKey> {
+        if base_bits == 0 || base_bits > MAX_BASE_BITS {
+            return Err(Error::InvalidBits {
+                bits: base_bits,
+                min: 1,
+                max: MAX_BASE_BITS,
+            });
+        }
+        Ok(self.build_key_switching_key(a, r2, p1_new, base_bits))
+    }
+
+    fn build_key_switching_key(
+        &self,
+        a: &ZZX,
+        r2: &ZZX,
+        p1_new: &ZZX,
+        base_bits: u32,
+    ) -> KeySwitchingKey {
+        let length = self.gadget_length(base_bits);
+        let mut b = Vec::with_capacity(length);
+        let mut k = Vec::with_capacity(length);
+
+        let base = ZZX::new_with_val(1u64 << base_bits);
+        let mut g_r2 = r2.clone();
+        for _ in 0..length {
+            self._mod(&mut g_r2);
+
+            let mut b_j = ZZX::new();
+            let mut k_j = ZZX::new();
+            self.encryption(&mut b_j, &mut k_j, a, p1_new, &g_r2);
+            b.push(b_j);
+            k.push(k_j);
+
+            g_r2 = &g_r2 * &base;
+        }
+
+        KeySwitchingKey { base_bits, b, k }
+    }
+
+    /// (c1', c2') decrypts under the new key to the same message as (c1, c2) under the old one.
+    /// Fails with `Error::WrongLength` if ksk was built for another modulus q.
+    pub fn key_switch(
+        &self,
+        c1_new: &mut ZZX,
+        c2_new: &mut ZZX,
+        c1: &ZZX,
+        c2: &ZZX,
+        ksk: &KeySwitchingKey,
+    ) -> Result<()> {
+        let mut c1_reduced = c1.clone();
+        self._mod(&mut c1_reduced);
+
+        let digits = self.gadget_decompose(&c1_reduced, ksk.base_bits);
+        if digits.len() != ksk.len() {
+            return Err(Error::WrongLength {
+                expected: digits.len(),
+                found: ksk.len(),
+            });
+        }
+
+        let mut acc1 = ZZX::new();
+        let mut acc2 = c2.clone();
+        for (j, d_j) in digits.iter().enumerate() {
+            acc1 += self.mulmod(d_j, &ksk.b[j]);
+            acc2 += self.mulmod(d_j, &ksk.k[j]);
+        }
+
+        self._mod(&mut acc1);
+        self._mod(&mut acc2);
+
+        *c1_new = acc1;
+        *c2_new = acc2;
+        Ok(())
+    }
+
+    /// Re-encryption key allowing a proxy to turn Alice's ciphertexts into Bob's
+    pub fn rekey(&self, sk_a: &RlweSecretKey, pk_b: &RlwePublicKey) -> ReKey {
+        self.build_key_switching_key(&pk_b.a, &sk_a.r2, &pk_b.p1, REKEY_BASE_BITS)
+    }
+
+    /// Alice's ciphertext ct_a turned into one for Bob, see `key_switch`
+    pub fn reencrypt(&self, rk: &ReKey, ct_a: &RlweCiphertext) -> Result<RlweCiphertext> {
+        let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+        self.key_switch(&mut c1, &mut c2, &ct_a.c1, &ct_a.c2, rk)?;
+        Ok(RlweCiphertext { c1, c2 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Float;
+
+    use super::*;
+    use crate::util::test_util::*;
+
+    #[test]
+    fn test_gadget_decompose() {
+        let es = scheme();
+        let a = uniform_poly();
+        let digits = es.gadget_decompose(&a, 5);
+        assert_eq!(digits.len(), 6);
+
+        let mut recomposed = ZZX::new();
+        for (j, d_j) in digits.iter().enumerate() {
+            recomposed += d_j * &ZZX::new_with_val(1u64 << (5 * j));
+        }
+        assert_eq!(recomposed, a);
+    }
+
+    #[test]
+    fn test_reencrypt() {
+        let es = scheme();
+        let a = uniform_poly();
+
+        let (mut r2_a, mut p1_a) = (ZZX::new(), ZZX::new());
+        es.key_generation(&a, &mut r2_a, &mut p1_a);
+        let (mut r2_b, mut p1_b) = (ZZX::new(), ZZX::new());
+        es.key_generation(&a, &mut r2_b, &mut p1_b);
+
+        let sk_a = RlweSecretKey { r2: r2_a };
+        let pk_b = RlwePublicKey {
+            a: a.clone(),
+            p1: p1_b,
+        };
+        let rk = es.rekey(&sk_a, &pk_b);
+
+        check_round_trips(&es, &a, &p1_a, 10, |c1_a, c2_a| {
+            let ct_a = RlweCiphertext {
+                c1: c1_a.clone(),
+                c2: c2_a.clone(),
+            };
+            let ct_b = es.reencrypt(&rk, &ct_a).unwrap();
+
+            let mut moriginal = ZZX::new();
+            es.decryption(&mut moriginal, &ct_b.c1, &ct_b.c2, &r2_b);
+            let mut mdecoded = vec![0; P as usize];
+            es.decode(&mut mdecoded, &moriginal);
+            mdecoded
+        });
+
+        // a key for another modulus has another number of digits
+        let sigma = Float::with_val(256, 2.0);
+        let other = EncryptionScheme::new(P, 12289, 256, 13.2, sigma, Float::with_val(256, 0));
+        let ct = RlweCiphertext {
+            c1: uniform_poly(),
+            c2: uniform_poly(),
+        };
+        assert_eq!(
+            other.reencrypt(&rk, &ct),
+            Err(Error::WrongLength {
+                expected: 4,
+                found: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_base_bits() {
+        let es = scheme();
+        let a = uniform_poly();
+        let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+        es.key_generation(&a, &mut r2, &mut p1);
+
+        for base_bits in [0, MAX_BASE_BITS + 1] {
+            assert_eq!(
+                es.key_switching_key(&a, &r2, &p1, base_bits).err(),
+                Some(Error::InvalidBits {
+                    bits: base_bits,
+                    min: 1,
+                    max: MAX_BASE_BITS
+                })
+            );
+        }
+        assert!(es.key_switching_key(&a, &r2, &p1, 8).is_ok());
+    }
+}
@@ -0,0 +1,25 @@
+pub mod drbg;
+pub mod encryption_scheme;
+pub mod envelope;
+pub mod error;
+pub mod failure;
+pub mod kat;
+pub mod key_exchange;
+pub mod key_switching;
+pub mod lwe;
+pub mod module_lwe;
+pub mod multikey;
+pub mod ntru_prime;
+pub mod params;
+pub mod pke;
+pub mod ring;
+pub mod sampling;
+pub mod sampling_table;
+pub mod serialization;
+pub mod shamir;
+pub mod signature;
+pub mod threshold;
+pub mod util;
+pub mod validation;
+
+pub use error::{Error, Result};
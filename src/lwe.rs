@@ -0,0 +1,383 @@
+use rug::{Float, Integer};
+
+use crate::{
+    error::{Error, Result},
+    pke::{Decrypt, Encrypt, KeyGen},
+    sampling::{sample_uniform_mod_q, Sampling},
+    util::{randombits_i64, RandomBits},
+};
+
+// Regev's LWE encryption (https://cims.nyu.edu/~regev/papers/lwesurvey.pdf) with
+// l-bit messages, as an unstructured baseline for `EncryptionScheme`.
+//
+// The secret S (n x l) and error E (m x l) are sampled from the same discrete
+// gaussian, and the public key is (A, B = A * S + E) for a uniform A in Z_q^{m x n}.
+// A message is encrypted under a random subset r in {0, 1}^m of the rows of A:
+//   u = A^T * r, v = B^T * r + encode(msg)
+// and v - S^T * u = E^T * r + encode(msg) decodes like the ring scheme.
+
+/// Dimension n of the secret, number m of samples in the public key and length l of messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LweParameters {
+    pub n: usize,
+    pub m: usize,
+    pub l: usize,
+    pub q: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LwePublicKey {
+    /* m x n */
+    pub a: Vec<Vec<i64>>,
+    /* m x l */
+    pub b: Vec<Vec<i64>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LweSecretKey {
+    /* n x l */
+    pub s: Vec<Vec<i64>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LweCiphertext {
+    pub u: Vec<i64>,
+    pub v: Vec<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LweScheme {
+    params: LweParameters,
+
+    /* Knuth-Yao discrete Gaussian sampler parameters */
+    tailcut: f32,
+    sigma: Float,
+    center: Float,
+
+    gauss: Sampling,
+}
+
+impl LweScheme {
+    fn matrix_sampling(&self, rows: usize, cols: usize) -> Vec<Vec<i64>> {
+        let mut bits = RandomBits::new();
+        let mut row = vec![0; cols];
+        (0..rows)
+            .map(|_| {
+                self.gauss.sample_into_with(&mut row, &mut bits);
+                row.iter().map(|&x| x as i64).collect()
+            })
+            .collect()
+    }
+
+    fn sample_bound(&self) -> i64 {
+        let bound = (self.tailcut * self.sigma.to_f32()).round() as i64;
+        bound - 1 + self.center.to_f32().round().abs() as i64
+    }
+
+    fn check_len(&self, len: usize, expected: usize) -> Result<()> {
+        if len != expected {
+            return Err(Error::WrongLength {
+                expected,
+                found: len,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_matrix(&self, a: &[Vec<i64>], rows: usize, cols: usize) -> Result<()> {
+        self.check_len(a.len(), rows)?;
+        for row in a.iter() {
+            self.check_len(row.len(), cols)?;
+        }
+        Ok(())
+    }
+
+    fn check_message_len(&self, len: usize) -> Result<()> {
+        if len != self.params.l {
+            return Err(Error::MessageLength {
+                expected: self.params.l,
+                found: len,
+            });
+        }
+        Ok(())
+    }
+
+    fn _mod(&self, a: i64) -> i64 {
+        a.rem_euclid(self.params.q as i64)
+    }
+}
+
+impl LweScheme {
+    /// Fails with `Error::InvalidParameters` if n, m or l is zero, and with
+    /// `Error::InvalidSampler` if the sampler cannot be built
+    pub fn new(
+        params: LweParameters,
+        precision: u32,
+        tailcut: f32,
+        sigma: Float,
+        center: Float,
+    ) -> Result<Self> {
+        if params.n == 0 || params.m == 0 || params.l == 0 {
+            return Err(Error::InvalidParameters("n, m and l must be positive"));
+        }
+
+        let gauss = Sampling::try_new(precision, tailcut, sigma.clone(), center.clone())?;
+
+        Ok(Self {
+            params,
+            tailcut,
+            sigma,
+            center,
+            gauss,
+        })
+    }
+
+    pub fn parameters(&self) -> LweParameters {
+        self.params
+    }
+
+    /// Worst case bound on the coefficients of the decryption noise E^T * r.
+    /// Decryption cannot fail while it is below q / 4.
+    pub fn noise_bound(&self) -> Integer {
+        Integer::from(self.params.m) * self.sample_bound()
+    }
+
+    /// Fails with `Error::WrongLength` if a is not an m x n matrix
+    pub fn key_generation(
+        &self,
+        a: &[Vec<i64>],
+        s: &mut Vec<Vec<i64>>,
+        b: &mut Vec<Vec<i64>>,
+    ) -> Result<()> {
+        let LweParameters { n, m, l, .. } = self.params;
+        self.check_matrix(a, m, n)?;
+
+        *s = self.matrix_sampling(n, l);
+        let e = self.matrix_sampling(m, l);
+
+        *b = (0..m)
+            .map(|i| {
+                (0..l)
+                    .map(|j| {
+                        let dot: i64 = (0..n).map(|k| self._mod(a[i][k] * s[k][j])).sum();
+                        self._mod(dot + e[i][j])
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Panics if a has fewer than l bits, see `try_encode`
+    pub fn encode(&self, aprime: &mut Vec<i64>, a: &[i32]) {
+        let bound = ((self.params.q - 1) / 2) as i64;
+        *aprime = a[..self.params.l]
+            .iter()
+            .map(|&a_i| a_i as i64 * bound)
+            .collect();
+    }
+
+    pub fn decode(&self, a: &mut [i32], aprime: &[i64]) {
+        let lbound = ((self.params.q - 1) / 4) as i64;
+        let ubound = 3 * lbound;
+
+        for (a_i, aprime_i) in a.iter_mut().zip(aprime.iter()).take(self.params.l) {
+            *a_i = (*aprime_i >= lbound && *aprime_i < ubound) as i32;
+        }
+    }
+
+    /// `encode`, or an error if a does not have l bits
+    pub fn try_encode(&self, aprime: &mut Vec<i64>, a: &[i32]) -> Result<()> {
+        self.check_message_len(a.len())?;
+        self.encode(aprime, a);
+        Ok(())
+    }
+
+    pub fn encryption(&self, u: &mut Vec<i64>, v: &mut Vec<i64>, pk: &LwePublicKey, m: &[i64]) {
+        let LweParameters { n, l, .. } = self.params;
+
+        *u = vec![0; n];
+        *v = m.to_vec();
+        // sums the rows of (A | B) selected by a uniform r in {0, 1}^m
+        for (a_i, b_i) in pk.a.iter().zip(pk.b.iter()) {
+            if randombits_i64(1) == 0 {
+                continue;
+            }
+            for (u_k, a_ik) in u.iter_mut().zip(a_i.iter()) {
+                *u_k = self._mod(*u_k + a_ik);
+            }
+            for (v_j, b_ij) in v.iter_mut().zip(b_i.iter()).take(l) {
+                *v_j = self._mod(*v_j + b_ij);
+            }
+        }
+    }
+
+    /// Fails with `Error::WrongLength` if u is not of length n, v of length l or s
+    /// an n x l matrix
+    pub fn decryption(&self, m: &mut Vec<i64>, u: &[i64], v: &[i64], s: &[Vec<i64>]) -> Result<()> {
+        let LweParameters { n, l, .. } = self.params;
+        self.check_len(u.len(), n)?;
+        self.check_len(v.len(), l)?;
+        self.check_matrix(s, n, l)?;
+
+        // v - S^T * u = E^T * r + m
+        *m = (0..l)
+            .map(|j| {
+                let dot: i64 = (0..n).map(|k| self._mod(s[k][j] * u[k])).sum();
+                self._mod(v[j] - dot)
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+impl KeyGen for LweScheme {
+    type PublicKey = LwePublicKey;
+    type SecretKey = LweSecretKey;
+
+    fn generate_keys(&self) -> (LwePublicKey, LweSecretKey) {
+        let mut bits = RandomBits::new();
+        let mut row = vec![0; self.params.n];
+        let a: Vec<Vec<i64>> = (0..self.params.m)
+            .map(|_| {
+                sample_uniform_mod_q(&mut row, self.params.q, &mut bits);
+                row.iter().map(|&x| x as i64).collect()
+            })
+            .collect();
+
+        let (mut s, mut b) = (vec![], vec![]);
+        self.key_generation(&a, &mut s, &mut b)
+            .expect("a is an m x n matrix");
+
+        (LwePublicKey { a, b }, LweSecretKey { s })
+    }
+}
+
+impl Encrypt for LweScheme {
+    type Ciphertext = LweCiphertext;
+
+    fn message_len(&self) -> usize {
+        self.params.l
+    }
+
+    fn encrypt(&self, pk: &LwePublicKey, msg: &[i32]) -> Result<LweCiphertext> {
+        let mut mprime = vec![];
+        self.try_encode(&mut mprime, msg)?;
+
+        let (mut u, mut v) = (vec![], vec![]);
+        self.encryption(&mut u, &mut v, pk, &mprime);
+
+        Ok(LweCiphertext { u, v })
+    }
+}
+
+impl Decrypt for LweScheme {
+    fn decrypt(&self, sk: &LweSecretKey, ct: &LweCiphertext) -> Result<Vec<i32>> {
+        for &c in ct.u.iter().chain(ct.v.iter()) {
+            if c < 0 || c >= self.params.q as i64 {
+                return Err(Error::ModulusMismatch {
+                    q: self.params.q.into(),
+                    found: c.into(),
+                });
+            }
+        }
+
+        let mut mprime = vec![];
+        self.decryption(&mut mprime, &ct.u, &ct.v, &sk.s)?;
+
+        let mut msg = vec![0; self.params.l];
+        self.decode(&mut msg, &mprime);
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pke::decryption_failures, util::test_util::*};
+
+    #[test]
+    fn test_lwe_encryption() {
+        let precision = 256;
+        let params = LweParameters {
+            n: 32,
+            m: 64,
+            l: P as usize,
+            q: Q,
+        };
+        let lwe = LweScheme::new(
+            params,
+            precision,
+            13.2,
+            Float::with_val(precision, 2.0),
+            Float::with_val(precision, 0),
+        )
+        .unwrap();
+        assert!(lwe.noise_bound() < Q / 4);
+
+        assert_eq!(decryption_failures(&lwe, 10), 0);
+
+        let mut mprime = vec![];
+        assert_eq!(
+            lwe.try_encode(&mut mprime, &random_message()[1..]),
+            Err(Error::MessageLength {
+                expected: P as usize,
+                found: P as usize - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_lwe_dimensions() {
+        let precision = 256;
+        let params = LweParameters {
+            n: 4,
+            m: 8,
+            l: 2,
+            q: Q,
+        };
+        let new = |params| {
+            let sigma = Float::with_val(precision, 2.0);
+            LweScheme::new(
+                params,
+                precision,
+                13.2,
+                sigma,
+                Float::with_val(precision, 0),
+            )
+        };
+        assert!(matches!(
+            new(LweParameters { n: 0, ..params }),
+            Err(Error::InvalidParameters(_))
+        ));
+
+        let lwe = new(params).unwrap();
+        let (mut s, mut b) = (vec![], vec![]);
+        assert_eq!(
+            lwe.key_generation(&vec![vec![0; 4]; 7], &mut s, &mut b),
+            Err(Error::WrongLength {
+                expected: 8,
+                found: 7
+            })
+        );
+
+        let (pk, mut sk) = lwe.generate_keys();
+        let ct = lwe.encrypt(&pk, &[1, 0]).unwrap();
+        sk.s[3].pop();
+        assert_eq!(
+            lwe.decrypt(&sk, &ct),
+            Err(Error::WrongLength {
+                expected: 2,
+                found: 1
+            })
+        );
+        sk.s.pop();
+        assert_eq!(
+            lwe.decrypt(&sk, &ct),
+            Err(Error::WrongLength {
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+}
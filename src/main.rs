@@ -1,109 +1,570 @@
-use encryption_scheme::EncryptionScheme;
-use rug::Float as RR;
-use util::{randombits_i64, randombits_u64, zzx::*};
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+    thread,
+    time::Instant,
+};
 
-mod encryption_scheme;
-mod sampling;
-mod util;
+use rlwe_rs::{
+    kat::{format_kats, generate_kats, KAT_COUNT},
+    params::{ParameterSet, PARAMETER_SETS, PRECISION, TAILCUT},
+    pke::KeyGen,
+    ring::RingModulus,
+    sampling::Sampling,
+    serialization::{from_bytes, to_bytes, Object},
+    util::{randombits_i64, zzx::ZZX},
+};
+use rug::Float;
 
-// // RLWE
-// const P: usize = 1024;
-// const Q: usize = 11289;
-// const SIGMA: f32 = 3.19;
+const USAGE: &str = "usage:
+    rlwe keygen --params <name> --out <key> [--insecure-test-params]
+                                              writes <key>.pub and <key>.sec
+    rlwe encrypt --pk <key.pub> [--insecure-test-params]
+                                              encrypts stdin to stdout
+    rlwe decrypt --sk <key.sec>               decrypts stdin to stdout
+    rlwe inspect <file>                       describes a key or an envelope
+    rlwe bench --params <name> [--iterations <n>]
+                                              times the scheme's operations
+    rlwe failures --params <name> [--trials <n>] [--trials-per-key <n>] [--threads <n>]
+                                              estimates the decryption failure rate
+    rlwe tables --out <dir>                   writes the sampler table of every parameter set
+    rlwe kat --out <dir> [--count <n>]        writes the known-answer tests of every parameter set";
 
-// ALTERNATE
-const P: usize = 14; // poly degree
-const Q: usize = 179424673; // 15485863, 8380417
-const SIGMA: f32 = 2.0;
+/* Shortest message (p bits) of a parameter set used for real data: the envelope key
+is derived from one message of the KEM, so it is no harder to guess than p bits */
+const MIN_MESSAGE_BITS: i32 = 256;
+const INSECURE_FLAG: &str = "--insecure-test-params";
 
-// // NTRU: NTRU Prime parameters
-// const P: usize = 761;
-// const Q: usize = 4591;
-// const SIGMA: f32 = 2.0;
+const BENCH_ITERATIONS: usize = 100;
+const FAILURE_TRIALS: usize = 10000;
+const TRIALS_PER_KEY: usize = 100;
 
-const BENCH_LOOPS: usize = 1000;
+/* Normal quantile of the reported confidence intervals (95%) */
+const CONFIDENCE_Z: f64 = 1.96;
+const HISTOGRAM_WIDTH: usize = 50;
 
-fn main() {
-    println!(
-        "----------------------------\nRing-LWE encryption scheme\n----------------------------\n"
-    );
+/* Exit codes, following sysexits.h */
+const EXIT_USAGE: u8 = 64;
+const EXIT_DATAERR: u8 = 65;
+const EXIT_NOINPUT: u8 = 66;
+const EXIT_IOERR: u8 = 74;
+
+enum CliError {
+    Usage(String),
+    /* a file that cannot be read */
+    NoInput(String, io::Error),
+    Io(String, io::Error),
+    /* malformed input, wrong key, failed authentication */
+    Data(String),
+}
 
-    let precision = 256;
-    let center = RR::with_val(precision, 0);
-    let tail_cut = 13.2;
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::NoInput(..) => EXIT_NOINPUT,
+            CliError::Io(..) => EXIT_IOERR,
+            CliError::Data(_) => EXIT_DATAERR,
+        }
+    }
+}
 
-    let mut total_errors = 0;
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            CliError::NoInput(path, e) => write!(f, "cannot read {}: {}", path, e),
+            CliError::Io(what, e) => write!(f, "{}: {}", what, e),
+            CliError::Data(msg) => write!(f, "{}", msg),
+        }
+    }
+}
 
-    let sigma = RR::with_val(precision, SIGMA);
-    let es = EncryptionScheme::new(P as i32, Q as i32, precision, tail_cut, sigma, center);
+impl From<rlwe_rs::Error> for CliError {
+    fn from(e: rlwe_rs::Error) -> Self {
+        CliError::Data(e.to_string())
+    }
+}
 
-    /* key generation */
-    let a = random_poly();
-    let mut r2 = ZZX::new();
-    let mut p1 = ZZX::new();
-    es.key_generation(&a, &mut r2, &mut p1);
+type CliResult<T> = Result<T, CliError>;
 
-    for _ in 0..BENCH_LOOPS {
-        let m = random_message();
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rlwe: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
 
-        // println!("Message being encrypted: {:?}\n", m);
+fn run(args: &[String]) -> CliResult<()> {
+    let (command, rest) = args
+        .split_first()
+        .ok_or_else(|| CliError::Usage("missing command".into()))?;
 
-        // encryption
-        let mut mprime = ZZX::new();
-        es.encode(&mut mprime, &m);
+    match command.as_str() {
+        "keygen" => {
+            let opts = Options::parse(rest, &["--params", "--out"], &[INSECURE_FLAG])?;
+            keygen(
+                opts.required("--params")?,
+                opts.required("--out")?,
+                opts.flag(INSECURE_FLAG),
+            )
+        }
+        "encrypt" => {
+            let opts = Options::parse(rest, &["--pk"], &[INSECURE_FLAG])?;
+            encrypt(opts.required("--pk")?, opts.flag(INSECURE_FLAG))
+        }
+        "decrypt" => {
+            let opts = Options::parse(rest, &["--sk"], &[])?;
+            decrypt(opts.required("--sk")?)
+        }
+        "inspect" => match rest {
+            [path] => inspect(path),
+            _ => Err(CliError::Usage("inspect takes exactly one file".into())),
+        },
+        "bench" => {
+            let opts = Options::parse(rest, &["--params", "--iterations"], &[])?;
+            let iterations = match opts.optional("--iterations") {
+                Some(n) => parse_count("--iterations", n)?,
+                None => BENCH_ITERATIONS,
+            };
+            bench(opts.required("--params")?, iterations)
+        }
+        "failures" => {
+            let opts = Options::parse(
+                rest,
+                &["--params", "--trials", "--trials-per-key", "--threads"],
+                &[],
+            )?;
+            let trials = match opts.optional("--trials") {
+                Some(n) => parse_count("--trials", n)?,
+                None => FAILURE_TRIALS,
+            };
+            let trials_per_key = match opts.optional("--trials-per-key") {
+                Some(n) => parse_count("--trials-per-key", n)?,
+                None => TRIALS_PER_KEY,
+            };
+            let threads = match opts.optional("--threads") {
+                Some(n) => parse_count("--threads", n)?,
+                None => thread::available_parallelism().map_or(1, |n| n.get()),
+            };
+            failures(opts.required("--params")?, trials, trials_per_key, threads)
+        }
+        "tables" => {
+            let opts = Options::parse(rest, &["--out"], &[])?;
+            tables(opts.required("--out")?)
+        }
+        "kat" => {
+            let opts = Options::parse(rest, &["--out", "--count"], &[])?;
+            let count = match opts.optional("--count") {
+                Some(n) => parse_count("--count", n)?,
+                None => KAT_COUNT,
+            };
+            kat(opts.required("--out")?, count)
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(CliError::Usage(format!("unknown command {:?}", command))),
+    }
+}
 
-        let mut c1 = ZZX::new();
-        let mut c2 = ZZX::new();
-        es.encryption(&mut c1, &mut c2, &a, &p1, &mprime);
+/// `--name value` pairs and `--name` flags, each name at most once
+struct Options<'a> {
+    values: Vec<(&'a str, &'a str)>,
+    flags: Vec<&'a str>,
+}
 
-        // println!("Encrypted message: {:?}\n", c1);
+impl<'a> Options<'a> {
+    fn parse(args: &'a [String], allowed: &[&str], allowed_flags: &[&str]) -> CliResult<Self> {
+        let mut values: Vec<(&str, &str)> = vec![];
+        let mut flags: Vec<&str> = vec![];
+        let mut args = args.iter();
+        while let Some(name) = args.next() {
+            if values.iter().any(|(n, _)| n == name) || flags.contains(&name.as_str()) {
+                return Err(CliError::Usage(format!("{} given twice", name)));
+            }
+            if allowed_flags.contains(&name.as_str()) {
+                flags.push(name);
+                continue;
+            }
+            if !allowed.contains(&name.as_str()) {
+                return Err(CliError::Usage(format!("unexpected argument {:?}", name)));
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| CliError::Usage(format!("{} needs a value", name)))?;
+            values.push((name, value));
+        }
+        Ok(Self { values, flags })
+    }
 
-        // decryption
-        let mut moriginal = ZZX::new();
-        es.decryption(&mut moriginal, &c1, &c2, &r2);
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(&name)
+    }
 
-        let mut mdecoded = vec![0; P];
-        es.decode(&mut mdecoded, &moriginal);
+    fn optional(&self, name: &str) -> Option<&'a str> {
+        self.values
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
 
-        // println!("Decrypted message: {:?}\n", mdecoded);
+    fn required(&self, name: &str) -> CliResult<&'a str> {
+        self.optional(name)
+            .ok_or_else(|| CliError::Usage(format!("missing {}", name)))
+    }
+}
 
-        let mut counter = 0;
-        for i in 0..P {
-            if mdecoded[i] != m[i] {
-                // println!("{}: {} != {}", i, mdecoded[i], m[i]);
-                counter += 1;
-            }
-        }
-        // println!("Number of incorrect decodings: {}\n", counter);
-        if counter > 0 {
-            total_errors += 1;
-        }
+/// A positive integer option
+fn parse_count(name: &str, value: &str) -> CliResult<usize> {
+    value
+        .parse()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| CliError::Usage(format!("{} must be a positive integer", name)))
+}
 
-        if total_errors == 0 {
-            println!("OK\n");
-        } else {
-            println!("{} executions have failed.", total_errors);
-        }
+fn parameter_set(name: &str) -> CliResult<&'static ParameterSet> {
+    ParameterSet::by_name(name).ok_or_else(|| {
+        let names: Vec<&str> = PARAMETER_SETS.iter().map(|params| params.name).collect();
+        CliError::Usage(format!(
+            "unknown parameter set {:?}, expected one of {}",
+            name,
+            names.join(", ")
+        ))
+    })
+}
 
-        if total_errors == BENCH_LOOPS {
-            println!("All executions have failed.");
-        }
+/// Refuses parameter sets whose messages are too short to protect data, unless
+/// `--insecure-test-params` is given
+fn check_secure(params: &ParameterSet, insecure: bool) -> CliResult<()> {
+    if params.p < MIN_MESSAGE_BITS && !insecure {
+        return Err(CliError::Usage(format!(
+            "parameter set {} encrypts {}-bit messages, below the {} bits needed to \
+             protect data; pass {} to use it for testing",
+            params.name, params.p, MIN_MESSAGE_BITS, INSECURE_FLAG
+        )));
     }
+    Ok(())
 }
 
-fn random_poly() -> ZZX {
-    let mut a = ZZX::new();
-    a.set_length(P);
-    for i in 0..P {
-        a[i] = randombits_u64(((Q as f64).log2() as u8) / 4).into();
+fn keygen(params_name: &str, out: &str, insecure: bool) -> CliResult<()> {
+    let params = parameter_set(params_name)?;
+    check_secure(params, insecure)?;
+    let es = params.scheme()?;
+    let (pk, sk) = es.generate_keys();
+
+    write_file(
+        &format!("{}.pub", out),
+        &to_bytes(params, &Object::PublicKey(pk))?,
+        false,
+    )?;
+    write_file(
+        &format!("{}.sec", out),
+        &to_bytes(params, &Object::SecretKey(sk))?,
+        true,
+    )
+}
+
+fn encrypt(pk_path: &str, insecure: bool) -> CliResult<()> {
+    let (params, pk) = match read_object(pk_path)? {
+        (params, Object::PublicKey(pk)) => (params, pk),
+        (_, object) => return Err(wrong_kind(pk_path, "public key", &object)),
+    };
+    check_secure(params, insecure)?;
+    let es = params.scheme()?;
+
+    let plaintext = read_stdin()?;
+    let envelope = es.seal(&pk, &plaintext)?;
+    write_stdout(&to_bytes(params, &Object::Envelope(envelope))?)
+}
+
+fn decrypt(sk_path: &str) -> CliResult<()> {
+    let (params, sk) = match read_object(sk_path)? {
+        (params, Object::SecretKey(sk)) => (params, sk),
+        (_, object) => return Err(wrong_kind(sk_path, "secret key", &object)),
+    };
+    let es = params.scheme()?;
+
+    let (envelope_params, envelope) = match from_bytes(&read_stdin()?)? {
+        (envelope_params, Object::Envelope(envelope)) => (envelope_params, envelope),
+        (_, object) => return Err(wrong_kind("stdin", "envelope", &object)),
+    };
+    if envelope_params != params {
+        return Err(CliError::Data(format!(
+            "the envelope uses parameter set {} but the key uses {}",
+            envelope_params.name, params.name
+        )));
     }
-    a
+
+    let plaintext = es.open(&sk, &envelope)?;
+    write_stdout(&plaintext)
 }
 
-fn random_message() -> Vec<i32> {
-    let mut a = Vec::new();
-    for _ in 0..P {
-        a.push(randombits_i64(1) as i32);
+fn inspect(path: &str) -> CliResult<()> {
+    let bytes = read_file(path)?;
+    let (params, object) = from_bytes(&bytes)?;
+
+    let ring = match params.modulus {
+        RingModulus::Cyclotomic => "x^p + 1",
+        RingModulus::NtruPrime => "x^p - x - 1",
+    };
+    println!("kind:       {}", object.kind_name());
+    println!(
+        "parameters: {} (p = {}, q = {}, sigma = {}, ring Z_q[x]/({}))",
+        params.name, params.p, params.q, params.sigma, ring
+    );
+    println!("size:       {} bytes", bytes.len());
+    if let Object::Envelope(envelope) = &object {
+        println!("payload:    {} bytes", envelope.body.len());
     }
-    a
+    Ok(())
+}
+
+fn bench(params_name: &str, iterations: usize) -> CliResult<()> {
+    let params = parameter_set(params_name)?;
+    let es = params.scheme()?;
+
+    let (pk, sk) = es.generate_keys();
+    let msg: Vec<i32> = (0..params.p).map(|_| randombits_i64(1) as i32).collect();
+    let mut m = ZZX::new();
+    es.encode(&mut m, &msg);
+    let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+    es.encryption(&mut c1, &mut c2, &pk.a, &pk.p1, &m);
+
+    println!(
+        "{} (p = {}, q = {}), {} iterations",
+        params.name, params.p, params.q, iterations
+    );
+    println!("{:<16} {:>14} {:>14}", "operation", "ops/sec", "cycles/op");
+
+    let report = |name: &str, op: &mut dyn FnMut()| {
+        let (ops_per_sec, cycles) = time_operation(iterations, op);
+        let cycles = cycles.map_or("n/a".to_string(), |c| format!("{:.0}", c));
+        println!("{:<16} {:>14.1} {:>14}", name, ops_per_sec, cycles);
+    };
+
+    let gauss = Sampling::new(
+        PRECISION,
+        TAILCUT,
+        Float::with_val(PRECISION, params.sigma),
+        Float::with_val(PRECISION, 0),
+    );
+    report("knuth_yao", &mut || {
+        gauss.knuth_yao();
+    });
+    report("key_generation", &mut || {
+        let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+        es.key_generation(&pk.a, &mut r2, &mut p1);
+    });
+    report("mulmod", &mut || {
+        es.mulmod(&pk.a, &sk.r2);
+    });
+    report("encryption", &mut || {
+        let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+        es.encryption(&mut c1, &mut c2, &pk.a, &pk.p1, &m);
+    });
+    report("decryption", &mut || {
+        let mut m = ZZX::new();
+        es.decryption(&mut m, &c1, &c2, &sk.r2);
+    });
+    Ok(())
+}
+
+fn failures(
+    params_name: &str,
+    trials: usize,
+    trials_per_key: usize,
+    threads: usize,
+) -> CliResult<()> {
+    let params = parameter_set(params_name)?;
+    let es = params.scheme()?;
+
+    // about eight bins per standard deviation of the predicted noise
+    let bin_width = ((es.noise_std_dev() / 8.0).round() as i64).max(1);
+    let report = es.failure_experiment(trials, trials_per_key, threads, bin_width);
+
+    println!(
+        "{} (p = {}, q = {}), {} trials under {} key pairs on {} threads",
+        params.name, params.p, params.q, trials, report.key_pairs, threads
+    );
+    let (lo, hi) = report.message_failure_interval(CONFIDENCE_Z);
+    println!(
+        "message failures:     {} ({:.3e}, 95% CI [{:.3e}, {:.3e}]), predicted {:.3e}",
+        report.failed_messages,
+        report.message_failure_rate(),
+        lo,
+        hi,
+        report.predicted_message_failure()
+    );
+    let (lo, hi) = report.coefficient_failure_interval(CONFIDENCE_Z);
+    println!(
+        "coefficient failures: {} ({:.3e}, 95% CI [{:.3e}, {:.3e}]), predicted {:.3e}",
+        report.failed_coefficients(),
+        report.coefficient_failure_rate(),
+        lo,
+        hi,
+        report.predicted_coefficient_failure
+    );
+    println!(
+        "noise std dev:        {:.2}, predicted {:.2}, worst case bound {} (q/4 = {})",
+        report.noise_std_dev(),
+        es.noise_std_dev(),
+        es.noise_bound(),
+        params.q / 4
+    );
+    let std_devs: Vec<(usize, f64)> = (0..params.p as usize)
+        .map(|i| (i, report.coefficient_noise_std_dev(i)))
+        .collect();
+    let (min_i, min) = std_devs
+        .iter()
+        .copied()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    let (max_i, max) = std_devs
+        .iter()
+        .copied()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    println!(
+        "per coefficient:      std dev from {:.2} (index {}) to {:.2} (index {})",
+        min, min_i, max, max_i
+    );
+
+    let failing: Vec<String> = report
+        .coefficient_errors
+        .iter()
+        .enumerate()
+        .filter(|(_, &errors)| errors > 0)
+        .map(|(i, errors)| format!("{}: {}", i, errors))
+        .collect();
+    if !failing.is_empty() {
+        println!("errors per coefficient: {}", failing.join(", "));
+    }
+
+    println!(
+        "noise histogram of all coefficients (bins of {}):",
+        bin_width
+    );
+    let histogram = report.noise_histogram();
+    let largest = histogram.values().max().copied().unwrap_or(1);
+    for (bin, count) in &histogram {
+        let bar = (count * HISTOGRAM_WIDTH).div_ceil(largest);
+        println!("{:>10} {:>12} {}", bin, count, "#".repeat(bar));
+    }
+    Ok(())
+}
+
+/// Computes the Knuth-Yao tables embedded by `params.rs`
+fn tables(dir: &str) -> CliResult<()> {
+    for params in PARAMETER_SETS {
+        let gauss = params.compute_sampler()?;
+        let path = format!("{}/{}.kyt", dir, params.name);
+        write_file(&path, &gauss.to_table_bytes(), false)?;
+        println!("{}", path);
+    }
+    Ok(())
+}
+
+/// Writes the known-answer tests checked by `tests/kat.rs`
+fn kat(dir: &str, count: usize) -> CliResult<()> {
+    for params in PARAMETER_SETS {
+        let records = generate_kats(&params, count)?;
+        let path = format!("{}/{}.rsp", dir, params.name);
+        write_file(&path, format_kats(&params, &records).as_bytes(), false)?;
+        println!("{}", path);
+    }
+    Ok(())
+}
+
+/// Operations per second and, on x86_64, cycles per operation
+fn time_operation(iterations: usize, op: &mut dyn FnMut()) -> (f64, Option<f64>) {
+    let start_cycles = cycle_counter();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        op();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let cycles = cycle_counter()
+        .zip(start_cycles)
+        .map(|(end, start)| (end - start) as f64 / iterations as f64);
+
+    (iterations as f64 / elapsed, cycles)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn cycle_counter() -> Option<u64> {
+    // SAFETY: rdtsc is available on every x86_64 processor
+    Some(unsafe { std::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cycle_counter() -> Option<u64> {
+    None
+}
+
+fn wrong_kind(path: &str, expected: &str, found: &Object) -> CliError {
+    CliError::Data(format!(
+        "{} is a {}, expected a {}",
+        path,
+        found.kind_name(),
+        expected
+    ))
+}
+
+fn read_object(path: &str) -> CliResult<(&'static ParameterSet, Object)> {
+    let bytes = read_file(path)?;
+    from_bytes(&bytes).map_err(|e| CliError::Data(format!("{}: {}", path, e)))
+}
+
+fn read_file(path: &str) -> CliResult<Vec<u8>> {
+    fs::read(path).map_err(|e| CliError::NoInput(path.into(), e))
+}
+
+fn read_stdin() -> CliResult<Vec<u8>> {
+    let mut input = vec![];
+    io::stdin()
+        .read_to_end(&mut input)
+        .map_err(|e| CliError::Io("cannot read stdin".into(), e))?;
+    Ok(input)
+}
+
+fn write_stdout(bytes: &[u8]) -> CliResult<()> {
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(bytes)
+        .and_then(|()| stdout.flush())
+        .map_err(|e| CliError::Io("cannot write stdout".into(), e))
+}
+
+/// Writes a file, readable by its owner only if `private`
+fn write_file(path: &str, bytes: &[u8], private: bool) -> CliResult<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(path)
+        .and_then(|mut file| {
+            // the mode only applies to new files, an existing one keeps its permissions
+            #[cfg(unix)]
+            if private {
+                use std::os::unix::fs::PermissionsExt;
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+            #[cfg(not(unix))]
+            let _ = private;
+            file.write_all(bytes)
+        })
+        .map_err(|e| CliError::Io(format!("cannot write {}", path), e))
 }
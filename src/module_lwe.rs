@@ -0,0 +1,290 @@
+use rug::Integer;
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    pke::{Decrypt, Encrypt, KeyGen},
+    util::{zzx::ZZX, RandomBits},
+};
+
+// Module-LWE variant of the scheme: the public `a` is a k x k matrix over
+// R_q = Z_q[x]/(f), and secrets and errors are vectors of k ring elements.
+// Security scales with k while the ring degree stays fixed.
+//
+//   key generation: p1 = r1 - A * r2
+//   encryption:     c1 = A^T * e1 + e2, c2 = <p1, e1> + e3 + m
+//   decryption:     <c1, r2> + c2 = m + <e2, r2> + <r1, e1> + e3
+
+/// Vector of k ring elements
+pub type PolyVec = Vec<ZZX>;
+
+/// k x k matrix of ring elements, stored by rows
+pub type PolyMatrix = Vec<Vec<ZZX>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModulePublicKey {
+    pub a: PolyMatrix,
+    pub p1: PolyVec,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleSecretKey {
+    pub r2: PolyVec,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleCiphertext {
+    pub c1: PolyVec,
+    pub c2: ZZX,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleEncryptionScheme {
+    es: EncryptionScheme,
+    k: usize,
+}
+
+impl ModuleEncryptionScheme {
+    fn vec_sampling(&self, a: &mut PolyVec) {
+        a.resize(self.k, ZZX::new());
+        let mut bits = RandomBits::new();
+        for a_i in a.iter_mut() {
+            self.es.poly_sampling_with(a_i, &mut bits);
+        }
+    }
+
+    /// <a, b> = sum_i a_i * b_i
+    fn inner_product(&self, a: &[ZZX], b: &[ZZX]) -> ZZX {
+        let mut acc = ZZX::new();
+        for (a_i, b_i) in a.iter().zip(b.iter()) {
+            acc += self.es.mulmod(a_i, b_i);
+        }
+        acc
+    }
+
+    fn check_len(&self, len: usize) -> Result<()> {
+        if len != self.k {
+            return Err(Error::WrongLength {
+                expected: self.k,
+                found: len,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_dimensions(&self, a: &PolyMatrix) -> Result<()> {
+        self.check_len(a.len())?;
+        for row in a.iter() {
+            self.check_len(row.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl ModuleEncryptionScheme {
+    pub fn new(es: EncryptionScheme, k: usize) -> Self {
+        assert!(k > 0, "k must be positive");
+        Self { es, k }
+    }
+
+    pub fn rank(&self) -> usize {
+        self.k
+    }
+
+    /// the underlying ring scheme, whose encode and decode apply unchanged
+    pub fn ring_scheme(&self) -> &EncryptionScheme {
+        &self.es
+    }
+
+    /// Worst case bound on the coefficients of the decryption noise, k times
+    /// that of the ring scheme (up to e3)
+    pub fn noise_bound(&self) -> Integer {
+        self.es.noise_bound() * self.k
+    }
+
+    /// Fails with `Error::WrongLength` if a is not a k x k matrix
+    pub fn key_generation(&self, a: &PolyMatrix, r2: &mut PolyVec, p1: &mut PolyVec) -> Result<()> {
+        self.check_dimensions(a)?;
+
+        let mut r1 = PolyVec::new();
+        self.vec_sampling(&mut r1);
+        self.vec_sampling(r2);
+
+        p1.clear();
+        for (a_i, r1_i) in a.iter().zip(r1.iter()) {
+            let mut p1_i = r1_i - self.inner_product(a_i, r2);
+            self.es._mod(&mut p1_i);
+            p1.push(p1_i);
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self, aprime: &mut ZZX, a: &[i32]) {
+        self.es.encode(aprime, a);
+    }
+
+    pub fn decode(&self, a: &mut [i32], aprime: &ZZX) {
+        self.es.decode(a, aprime);
+    }
+
+    /// Fails with `Error::WrongLength` if a is not a k x k matrix or p1 not of length k
+    pub fn encryption(
+        &self,
+        c1: &mut PolyVec,
+        c2: &mut ZZX,
+        a: &PolyMatrix,
+        p1: &PolyVec,
+        m: &ZZX,
+    ) -> Result<()> {
+        self.check_dimensions(a)?;
+        self.check_len(p1.len())?;
+
+        let mut e1 = PolyVec::new();
+        let mut e2 = PolyVec::new();
+        let mut e3 = ZZX::new();
+        self.vec_sampling(&mut e1);
+        self.vec_sampling(&mut e2);
+        self.es.poly_sampling(&mut e3);
+
+        // c1 = A^T * e1 + e2
+        c1.clear();
+        for (j, e2_j) in e2.into_iter().enumerate() {
+            let column: Vec<ZZX> = a.iter().map(|row| row[j].clone()).collect();
+            let mut c1_j = self.inner_product(&column, &e1) + e2_j;
+            self.es._mod(&mut c1_j);
+            c1.push(c1_j);
+        }
+
+        *c2 = self.inner_product(p1, &e1) + e3 + m;
+        self.es._mod(c2);
+        Ok(())
+    }
+
+    /// Fails with `Error::WrongLength` if c1 or r2 is not of length k
+    pub fn decryption(&self, m: &mut ZZX, c1: &PolyVec, c2: &ZZX, r2: &PolyVec) -> Result<()> {
+        self.check_len(c1.len())?;
+        self.check_len(r2.len())?;
+
+        *m = self.inner_product(c1, r2) + c2;
+        self.es._mod(m);
+        Ok(())
+    }
+}
+
+impl KeyGen for ModuleEncryptionScheme {
+    type PublicKey = ModulePublicKey;
+    type SecretKey = ModuleSecretKey;
+
+    fn generate_keys(&self) -> (ModulePublicKey, ModuleSecretKey) {
+        let a: PolyMatrix = (0..self.k)
+            .map(|_| (0..self.k).map(|_| self.es.uniform_poly()).collect())
+            .collect();
+        let (mut r2, mut p1) = (PolyVec::new(), PolyVec::new());
+        self.key_generation(&a, &mut r2, &mut p1)
+            .expect("a is a k x k matrix");
+
+        (ModulePublicKey { a, p1 }, ModuleSecretKey { r2 })
+    }
+}
+
+impl Encrypt for ModuleEncryptionScheme {
+    type Ciphertext = ModuleCiphertext;
+
+    fn message_len(&self) -> usize {
+        self.es.p as usize
+    }
+
+    fn encrypt(&self, pk: &ModulePublicKey, msg: &[i32]) -> Result<ModuleCiphertext> {
+        let mut mprime = ZZX::new();
+        self.es.try_encode(&mut mprime, msg)?;
+
+        let (mut c1, mut c2) = (PolyVec::new(), ZZX::new());
+        self.encryption(&mut c1, &mut c2, &pk.a, &pk.p1, &mprime)?;
+
+        Ok(ModuleCiphertext { c1, c2 })
+    }
+}
+
+impl Decrypt for ModuleEncryptionScheme {
+    fn decrypt(&self, sk: &ModuleSecretKey, ct: &ModuleCiphertext) -> Result<Vec<i32>> {
+        self.check_len(ct.c1.len())?;
+        self.check_len(sk.r2.len())?;
+
+        // <c1, r2> + c2 is decrypted by the ring scheme one term at a time
+        let mut mprime = ct.c2.clone();
+        for (c1_i, r2_i) in ct.c1.iter().zip(sk.r2.iter()) {
+            let mut term = ZZX::new();
+            self.es.try_decryption(&mut term, c1_i, &mprime, r2_i)?;
+            mprime = term;
+        }
+
+        let mut msg = vec![0; self.es.p as usize];
+        self.decode(&mut msg, &mprime);
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Float;
+
+    use super::*;
+    use crate::{pke::decryption_failures, util::test_util::*};
+
+    #[test]
+    fn test_module_encryption() {
+        for k in 1..=3 {
+            let mes = ModuleEncryptionScheme::new(scheme(), k);
+            assert!(mes.noise_bound() < Q / 4);
+            assert_eq!(decryption_failures(&mes, 5), 0);
+        }
+    }
+
+    /// Security scales with k at the fixed ring degree n = 256
+    #[test]
+    fn test_module_encryption_n256() {
+        let precision = 256;
+        let sigma = Float::with_val(precision, 2.0);
+        let center = Float::with_val(precision, 0);
+        let es = EncryptionScheme::new(256, 12289, precision, 13.2, sigma, center);
+        for k in [2, 3] {
+            let mes = ModuleEncryptionScheme::new(es.clone(), k);
+            assert_eq!(decryption_failures(&mes, 5), 0);
+        }
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let k = 2;
+        let mes = ModuleEncryptionScheme::new(scheme(), k);
+        let mut a: PolyMatrix = (0..k)
+            .map(|_| (0..k).map(|_| uniform_poly()).collect())
+            .collect();
+        let (mut r2, mut p1) = (PolyVec::new(), PolyVec::new());
+        mes.key_generation(&a, &mut r2, &mut p1).unwrap();
+
+        let (mut c1, mut c2) = (PolyVec::new(), ZZX::new());
+        mes.encryption(&mut c1, &mut c2, &a, &p1, &ZZX::new())
+            .unwrap();
+        let mut m = ZZX::new();
+        assert_eq!(
+            mes.decryption(&mut m, &c1[1..].to_vec(), &c2, &r2),
+            Err(Error::WrongLength {
+                expected: k,
+                found: k - 1
+            })
+        );
+
+        a[1].pop();
+        assert_eq!(
+            mes.key_generation(&a, &mut r2, &mut p1),
+            Err(Error::WrongLength {
+                expected: k,
+                found: k - 1
+            })
+        );
+        assert!(mes
+            .encryption(&mut c1, &mut c2, &a, &p1, &ZZX::new())
+            .is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    util::zzx::ZZX,
+};
+
+// Multi-key encryption: since `a` is public and shared, every party can
+// generate p1_i = r1_i - a * r2_i on its own. The sum of the public keys is a
+// public key for r2 = sum_i r2_i, so a ciphertext under the joint key can only
+// be decrypted with the partial decryptions of all parties.
+
+/// Aggregate of the public keys of several independent parties
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointPublicKey {
+    p1: ZZX,
+    parties: usize,
+}
+
+impl JointPublicKey {
+    pub fn p1(&self) -> &ZZX {
+        &self.p1
+    }
+
+    /// number of partial decryptions needed to decrypt
+    pub fn parties(&self) -> usize {
+        self.parties
+    }
+}
+
+impl EncryptionScheme {
+    /// Fails with `Error::InvalidShares` if there are no public keys
+    pub fn aggregate_public_keys(&self, public_keys: &[ZZX]) -> Result<JointPublicKey> {
+        let mut p1 = ZZX::new();
+        self.dkg_public_key(&mut p1, public_keys)?;
+
+        Ok(JointPublicKey {
+            p1,
+            parties: public_keys.len(),
+        })
+    }
+
+    pub fn multikey_encryption(
+        &self,
+        c1: &mut ZZX,
+        c2: &mut ZZX,
+        a: &ZZX,
+        jpk: &JointPublicKey,
+        m: &ZZX,
+    ) {
+        self.encryption(c1, c2, a, &jpk.p1, m);
+    }
+
+    /// Decodes a ciphertext under `jpk` from the partial decryptions
+    /// (see `partial_decryption`) of every party, or fails with
+    /// `Error::WrongLength` if some are missing
+    pub fn multikey_decryption(
+        &self,
+        m: &mut [i32],
+        c2: &ZZX,
+        partials: &[ZZX],
+        jpk: &JointPublicKey,
+    ) -> Result<()> {
+        if partials.len() != jpk.parties {
+            return Err(Error::WrongLength {
+                expected: jpk.parties,
+                found: partials.len(),
+            });
+        }
+
+        self.combine_partial_decryptions(m, c2, partials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::*;
+
+    const PARTIES: usize = 4;
+    const SMUDGING_BITS: u8 = 16;
+
+    #[test]
+    fn test_multikey_encryption() {
+        let es = scheme();
+        let a = uniform_poly();
+
+        let mut secret_keys = vec![];
+        let mut public_keys = vec![];
+        for _ in 0..PARTIES {
+            let (mut r2_i, mut p1_i) = (ZZX::new(), ZZX::new());
+            es.key_generation(&a, &mut r2_i, &mut p1_i);
+            secret_keys.push(r2_i);
+            public_keys.push(p1_i);
+        }
+        let jpk = es.aggregate_public_keys(&public_keys).unwrap();
+        assert_eq!(jpk.parties(), PARTIES);
+
+        let mut missing_one_failed = false;
+        check_round_trips(&es, &a, jpk.p1(), 5, |c1, c2| {
+            let partials: Vec<ZZX> = secret_keys
+                .iter()
+                .map(|r2_i| {
+                    let mut d_i = ZZX::new();
+                    es.partial_decryption(&mut d_i, c1, r2_i, SMUDGING_BITS)
+                        .unwrap();
+                    d_i
+                })
+                .collect();
+
+            let mut mdecoded = vec![0; P as usize];
+            es.multikey_decryption(&mut mdecoded, c2, &partials, &jpk)
+                .unwrap();
+            assert_eq!(
+                es.multikey_decryption(&mut vec![0; P as usize], c2, &partials[1..], &jpk),
+                Err(Error::WrongLength {
+                    expected: PARTIES,
+                    found: PARTIES - 1
+                })
+            );
+
+            let mut missing_one = vec![0; P as usize];
+            es.combine_partial_decryptions(&mut missing_one, c2, &partials[1..])
+                .unwrap();
+            missing_one_failed |= missing_one != mdecoded;
+            mdecoded
+        });
+        assert!(missing_one_failed);
+    }
+}
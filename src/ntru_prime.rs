@@ -0,0 +1,382 @@
+use rug::Integer;
+use sha3::{Digest, Sha3_512};
+
+use crate::{
+    drbg::randombytes,
+    error::{Error, Result},
+    pke::{Kem, KeyGen, SHARED_SECRET_BYTES},
+    ring::RingModulus,
+    sampling::{sample_ternary, sample_uniform_mod_q},
+    util::{
+        ct_eq,
+        zzx::{invmod, ZZX},
+        RandomBits,
+    },
+};
+
+// Streamlined NTRU Prime KEM (https://ntruprime.cr.yp.to/nist/ntruprime-20201007.pdf)
+// over R = Z[x]/(x^p - x - 1).
+//
+// Key generation picks a short f and a small g invertible in R/3, and publishes
+// h = g / (3f) in R/q. Encryption rounds h * r to multiples of 3, for a short r.
+// Decryption recovers r from 3f * c = g * r + 3f * e mod q, then mod 3.
+//
+// The algebra follows the specification, but hashes use SHA3-512 and the byte
+// encodings are simpler, so keys and ciphertexts are not interoperable with sntrup761.
+
+/// Ring degree p, prime modulus q and weight w of short polynomials.
+/// Decryption never fails when q >= 16w + 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NtruPrimeParameters {
+    pub p: usize,
+    pub q: i64,
+    pub w: usize,
+}
+
+/// The sntrup761 parameter set
+pub const SNTRUP761: NtruPrimeParameters = NtruPrimeParameters {
+    p: 761,
+    q: 4591,
+    w: 286,
+};
+
+/// Length in bytes of the shared secrets and of the plaintext confirmation
+pub const NTRU_PRIME_HASH_BYTES: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NtruPrimePublicKey {
+    h: ZZX,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NtruPrimeSecretKey {
+    f: ZZX,
+    /* 1/g in R/3 */
+    v: ZZX,
+    pk: NtruPrimePublicKey,
+    /* returned instead of the session key on decapsulation failure */
+    rho: [u8; NTRU_PRIME_HASH_BYTES],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NtruPrimeCiphertext {
+    c: ZZX,
+    confirm: [u8; NTRU_PRIME_HASH_BYTES],
+}
+
+impl NtruPrimePublicKey {
+    pub fn h(&self) -> &ZZX {
+        &self.h
+    }
+}
+
+impl NtruPrimeCiphertext {
+    pub fn c(&self) -> &ZZX {
+        &self.c
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NtruPrime {
+    params: NtruPrimeParameters,
+    f: ZZX,
+}
+
+impl NtruPrime {
+    /// a * b in R/m, with coefficients in (-m/2, m/2]
+    fn mulmod(&self, a: &ZZX, b: &ZZX, m: i64) -> ZZX {
+        let c = RingModulus::NtruPrime.reduce(&(a * b), self.params.p);
+        centered(&c, m)
+    }
+
+    /// r with w coefficients in {-1, 1} and all others 0
+    fn short_random(&self) -> ZZX {
+        let mut r = vec![0; self.params.p];
+        sample_ternary(&mut r, self.params.w, &mut RandomBits::new());
+        ZZX::new_with_vec(r)
+    }
+
+    /// g with coefficients uniform in {-1, 0, 1}
+    fn small_random(&self) -> ZZX {
+        let mut g = vec![0; self.params.p];
+        sample_uniform_mod_q(&mut g, 3, &mut RandomBits::new());
+        ZZX::new_with_vec(g.into_iter().map(|x| x - 1).collect())
+    }
+
+    /// Rounds every coefficient of a (centered mod q) to the nearest multiple of 3
+    fn round(&self, a: &ZZX) -> ZZX {
+        let coeffs: Vec<i64> = (0..self.params.p)
+            .map(|i| {
+                let a_i = a.coeff(i).to_i64().unwrap();
+                3 * (a_i + 1).div_euclid(3)
+            })
+            .collect();
+        ZZX::new_with_vec(coeffs)
+    }
+
+    fn weight(&self, r: &ZZX) -> usize {
+        (0..self.params.p).filter(|&i| r.coeff(i) != 0).count()
+    }
+
+    fn encrypt(&self, r: &ZZX, pk: &NtruPrimePublicKey) -> ZZX {
+        self.round(&self.mulmod(&pk.h, r, self.params.q))
+    }
+
+    fn decrypt(&self, c: &ZZX, sk: &NtruPrimeSecretKey) -> ZZX {
+        // 3f * c = g * r + 3f * e (mod q), whose coefficients are small enough to be lifted
+        let f3 = &sk.f * &ZZX::new_with_val(3);
+        let e = self.mulmod(&f3, c, self.params.q);
+        let e = centered(&e, 3);
+        let r = self.mulmod(&e, &sk.v, 3);
+
+        if self.weight(&r) == self.params.w {
+            r
+        } else {
+            // any fixed short polynomial, so that the re-encryption check fails
+            let mut r = ZZX::new();
+            for i in 0..self.params.w {
+                r[i] = 1.into();
+            }
+            r
+        }
+    }
+
+    fn encode_rq(&self, a: &ZZX) -> Vec<u8> {
+        (0..self.params.p)
+            .flat_map(|i| {
+                let a_i = a.coeff(i).to_i64().unwrap().rem_euclid(self.params.q);
+                (a_i as u16).to_le_bytes()
+            })
+            .collect()
+    }
+
+    fn encode_small(&self, a: &ZZX) -> Vec<u8> {
+        (0..self.params.p)
+            .map(|i| (a.coeff(i).to_i64().unwrap() + 1) as u8)
+            .collect()
+    }
+
+    fn hash(&self, prefix: u8, chunks: &[&[u8]]) -> [u8; NTRU_PRIME_HASH_BYTES] {
+        let mut hasher = Sha3_512::new();
+        hasher.update([prefix]);
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        let digest = hasher.finalize();
+
+        let mut out = [0u8; NTRU_PRIME_HASH_BYTES];
+        out.copy_from_slice(&digest[..NTRU_PRIME_HASH_BYTES]);
+        out
+    }
+
+    fn hash_confirm(&self, r: &ZZX, pk: &NtruPrimePublicKey) -> [u8; NTRU_PRIME_HASH_BYTES] {
+        let pk_hash = self.hash(4, &[&self.encode_rq(&pk.h)]);
+        self.hash(2, &[&self.encode_small(r), &pk_hash])
+    }
+
+    fn hash_session(
+        &self,
+        b: u8,
+        y: &[u8],
+        ct: &NtruPrimeCiphertext,
+    ) -> [u8; NTRU_PRIME_HASH_BYTES] {
+        self.hash(b, &[y, &self.encode_rq(&ct.c), &ct.confirm])
+    }
+}
+
+impl NtruPrime {
+    /// Fails with `Error::InvalidParameters` unless 1 <= w <= p and q is 1 mod 6 and
+    /// fits in 16 bits
+    pub fn new(params: NtruPrimeParameters) -> Result<Self> {
+        if params.w == 0 || params.w > params.p {
+            return Err(Error::InvalidParameters("w must be between 1 and p"));
+        }
+        if params.q % 6 != 1 || params.q >= 1 << 16 {
+            return Err(Error::InvalidParameters(
+                "q must be 1 mod 6 and fit in 16 bits",
+            ));
+        }
+
+        let f = RingModulus::NtruPrime.polynomial(params.p);
+        Ok(Self { params, f })
+    }
+
+    pub fn parameters(&self) -> NtruPrimeParameters {
+        self.params
+    }
+
+    pub fn key_generation(&self) -> (NtruPrimePublicKey, NtruPrimeSecretKey) {
+        let (g, v) = loop {
+            let g = self.small_random();
+            if let Some(v) = invmod(&g, &self.f, &Integer::from(3)) {
+                break (g, v);
+            }
+        };
+        let (f, f3_inv) = loop {
+            let f = self.short_random();
+            let f3 = &f * &ZZX::new_with_val(3);
+            if let Some(f3_inv) = invmod(&f3, &self.f, &Integer::from(self.params.q)) {
+                break (f, f3_inv);
+            }
+        };
+
+        let pk = NtruPrimePublicKey {
+            h: self.mulmod(&g, &f3_inv, self.params.q),
+        };
+        let sk = NtruPrimeSecretKey {
+            f,
+            v: centered(&v, 3),
+            pk: pk.clone(),
+            rho: {
+                let mut rho = [0; NTRU_PRIME_HASH_BYTES];
+                randombytes(&mut rho);
+                rho
+            },
+        };
+
+        (pk, sk)
+    }
+
+    /// Returns a ciphertext and the session key it encapsulates
+    pub fn encapsulate(
+        &self,
+        pk: &NtruPrimePublicKey,
+    ) -> (NtruPrimeCiphertext, [u8; NTRU_PRIME_HASH_BYTES]) {
+        let r = self.short_random();
+        let ct = NtruPrimeCiphertext {
+            c: self.encrypt(&r, pk),
+            confirm: self.hash_confirm(&r, pk),
+        };
+        let key = self.hash_session(1, &self.encode_small(&r), &ct);
+
+        (ct, key)
+    }
+
+    /// Returns the session key, or a pseudorandom key derived from the secret rho
+    /// when the ciphertext is not a valid encryption (implicit rejection)
+    pub fn decapsulate(
+        &self,
+        sk: &NtruPrimeSecretKey,
+        ct: &NtruPrimeCiphertext,
+    ) -> [u8; NTRU_PRIME_HASH_BYTES] {
+        let r = self.decrypt(&ct.c, sk);
+
+        let c = self.encrypt(&r, &sk.pk);
+        let confirm = self.hash_confirm(&r, &sk.pk);
+
+        // both keys are computed and selected with a mask, so that the time does not
+        // reveal whether the ciphertext was rejected
+        let valid =
+            ct_eq(&self.encode_rq(&c), &self.encode_rq(&ct.c)) & ct_eq(&confirm, &ct.confirm);
+        let accepted = self.hash_session(1, &self.encode_small(&r), ct);
+        let rejected = self.hash_session(0, &sk.rho, ct);
+        let mask = (valid as u8).wrapping_neg();
+        std::array::from_fn(|i| (accepted[i] & mask) | (rejected[i] & !mask))
+    }
+}
+
+impl KeyGen for NtruPrime {
+    type PublicKey = NtruPrimePublicKey;
+    type SecretKey = NtruPrimeSecretKey;
+
+    fn generate_keys(&self) -> (NtruPrimePublicKey, NtruPrimeSecretKey) {
+        self.key_generation()
+    }
+}
+
+impl Kem for NtruPrime {
+    type Encapsulation = NtruPrimeCiphertext;
+
+    fn encapsulate(
+        &self,
+        pk: &NtruPrimePublicKey,
+    ) -> Result<(NtruPrimeCiphertext, [u8; SHARED_SECRET_BYTES])> {
+        Ok(NtruPrime::encapsulate(self, pk))
+    }
+
+    fn decapsulate(
+        &self,
+        sk: &NtruPrimeSecretKey,
+        ct: &NtruPrimeCiphertext,
+    ) -> Result<[u8; SHARED_SECRET_BYTES]> {
+        Ok(NtruPrime::decapsulate(self, sk, ct))
+    }
+}
+
+/// a with coefficients reduced into (-m/2, m/2]
+fn centered(a: &ZZX, m: i64) -> ZZX {
+    let coeffs: Vec<i64> = (0..=a.deg().max(0) as usize)
+        .map(|i| {
+            let a_i = a.coeff(i).to_i64().unwrap().rem_euclid(m);
+            if 2 * a_i > m {
+                a_i - m
+            } else {
+                a_i
+            }
+        })
+        .collect();
+    ZZX::new_with_vec(coeffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Toy parameters, q >= 16w + 1
+    const TOY: NtruPrimeParameters = NtruPrimeParameters {
+        p: 31,
+        q: 139,
+        w: 8,
+    };
+
+    #[test]
+    fn test_encapsulate_decapsulate() {
+        let kem = NtruPrime::new(TOY).unwrap();
+        let (pk, sk) = kem.key_generation();
+
+        for _ in 0..10 {
+            let (ct, key) = kem.encapsulate(&pk);
+            assert_eq!(kem.decapsulate(&sk, &ct), key);
+        }
+    }
+
+    #[test]
+    fn test_sntrup761() {
+        let kem = NtruPrime::new(SNTRUP761).unwrap();
+        let (pk, sk) = kem.key_generation();
+
+        let (mut ct, key) = kem.encapsulate(&pk);
+        assert_eq!(kem.decapsulate(&sk, &ct), key);
+        ct.confirm[0] ^= 1;
+        assert_ne!(kem.decapsulate(&sk, &ct), key);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        for params in [
+            NtruPrimeParameters { w: 0, ..TOY },
+            NtruPrimeParameters { w: 32, ..TOY },
+            NtruPrimeParameters { q: 137, ..TOY },
+            NtruPrimeParameters { q: 65557, ..TOY },
+        ] {
+            assert!(matches!(
+                NtruPrime::new(params),
+                Err(Error::InvalidParameters(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_implicit_rejection() {
+        let kem = NtruPrime::new(TOY).unwrap();
+        let (pk, sk) = kem.key_generation();
+
+        let (mut ct, key) = kem.encapsulate(&pk);
+        ct.confirm[0] ^= 1;
+
+        let rejected = kem.decapsulate(&sk, &ct);
+        assert_ne!(rejected, key);
+        // the same invalid ciphertext always gets the same key
+        assert_eq!(kem.decapsulate(&sk, &ct), rejected);
+    }
+}
@@ -0,0 +1,108 @@
+use rug::Float;
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    ring::RingModulus,
+    sampling::Sampling,
+};
+
+/// Named parameters of an `EncryptionScheme`, as referred to by serialized keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterSet {
+    pub name: &'static str,
+    pub p: i32,
+    pub q: i32,
+    pub sigma: f32,
+    pub modulus: RingModulus,
+    /// Precomputed Knuth-Yao table (see `sampling_table.rs`), regenerated with
+    /// `rlwe tables --out tables`
+    pub table: &'static [u8],
+}
+
+/// Sampler settings shared by every parameter set
+pub const PRECISION: u32 = 256;
+pub const TAILCUT: f32 = 13.2;
+
+pub const RLWE1024: ParameterSet = ParameterSet {
+    name: "rlwe1024",
+    p: 1024,
+    q: 11289,
+    sigma: 3.19,
+    modulus: RingModulus::Cyclotomic,
+    table: include_bytes!("../tables/rlwe1024.kyt"),
+};
+
+/// The ALTERNATE set of `main.rs`, small enough for tests
+pub const TOY: ParameterSet = ParameterSet {
+    name: "toy",
+    p: 14,
+    q: 179424673,
+    sigma: 2.0,
+    modulus: RingModulus::Cyclotomic,
+    table: include_bytes!("../tables/toy.kyt"),
+};
+
+pub const NTRUP761: ParameterSet = ParameterSet {
+    name: "ntrup761",
+    p: 761,
+    q: 4591,
+    sigma: 2.0,
+    modulus: RingModulus::NtruPrime,
+    table: include_bytes!("../tables/ntrup761.kyt"),
+};
+
+pub const PARAMETER_SETS: [ParameterSet; 3] = [RLWE1024, TOY, NTRUP761];
+
+impl ParameterSet {
+    pub fn by_name(name: &str) -> Option<&'static ParameterSet> {
+        PARAMETER_SETS.iter().find(|params| params.name == name)
+    }
+
+    pub fn scheme(&self) -> Result<EncryptionScheme> {
+        EncryptionScheme::try_with_sampler(self.p, self.q, self.sampler()?, self.modulus)
+    }
+
+    /// The sampler loaded from `table`
+    pub fn sampler(&self) -> Result<Sampling> {
+        let gauss = Sampling::from_table_bytes(self.table)?;
+        if gauss.precision() != PRECISION
+            || gauss.tailcut() != TAILCUT
+            || *gauss.sigma() != Float::with_val(PRECISION, self.sigma)
+            || !gauss.center().is_zero()
+        {
+            return Err(Error::Format(
+                "sampler table does not match the parameter set",
+            ));
+        }
+        Ok(gauss)
+    }
+
+    /// The sampler computed from scratch, as written to `table`
+    pub fn compute_sampler(&self) -> Result<Sampling> {
+        Sampling::try_new(
+            PRECISION,
+            TAILCUT,
+            Float::with_val(PRECISION, self.sigma),
+            Float::with_val(PRECISION, 0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_tables() {
+        for params in PARAMETER_SETS {
+            let gauss = params.compute_sampler().unwrap();
+            assert!(
+                params.table == gauss.to_table_bytes(),
+                "the table of {} is out of date",
+                params.name
+            );
+            assert!(params.scheme().is_ok());
+        }
+    }
+}
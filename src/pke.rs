@@ -0,0 +1,215 @@
+// Interfaces shared by the schemes of the crate, so that application code,
+// benchmarks and correctness checks can be generic over the scheme and its parameters.
+//
+// Messages are slices of `message_len()` bits, encoded as in `EncryptionScheme::encode`.
+// Encryption and decryption return an error on malformed messages, keys or ciphertexts.
+
+use crate::{error::Result, util::randombits_i64};
+
+/// Length in bytes of the shared secrets of a `Kem`
+pub const SHARED_SECRET_BYTES: usize = 32;
+
+pub trait KeyGen {
+    type PublicKey;
+    type SecretKey;
+
+    /// Samples the public parameters and a fresh key pair
+    fn generate_keys(&self) -> (Self::PublicKey, Self::SecretKey);
+}
+
+pub trait Encrypt: KeyGen {
+    type Ciphertext;
+
+    /// Number of bits of a message
+    fn message_len(&self) -> usize;
+
+    fn encrypt(&self, pk: &Self::PublicKey, msg: &[i32]) -> Result<Self::Ciphertext>;
+}
+
+pub trait Decrypt: Encrypt {
+    fn decrypt(&self, sk: &Self::SecretKey, ct: &Self::Ciphertext) -> Result<Vec<i32>>;
+}
+
+/// Public key encryption, implemented by every scheme with `KeyGen`, `Encrypt` and `Decrypt`
+pub trait Pke: KeyGen + Encrypt + Decrypt {}
+
+impl<S: KeyGen + Encrypt + Decrypt> Pke for S {}
+
+pub trait Kem: KeyGen {
+    type Encapsulation;
+
+    /// Returns an encapsulation and the shared secret it carries
+    fn encapsulate(
+        &self,
+        pk: &Self::PublicKey,
+    ) -> Result<(Self::Encapsulation, [u8; SHARED_SECRET_BYTES])>;
+
+    fn decapsulate(
+        &self,
+        sk: &Self::SecretKey,
+        ct: &Self::Encapsulation,
+    ) -> Result<[u8; SHARED_SECRET_BYTES]>;
+}
+
+/// Encrypts and decrypts `trials` random messages under a fresh key pair,
+/// returning the number of messages that did not decrypt correctly (or at all)
+pub fn decryption_failures<S: Pke>(scheme: &S, trials: usize) -> usize {
+    let (pk, sk) = scheme.generate_keys();
+
+    (0..trials)
+        .filter(|_| {
+            let msg: Vec<i32> = (0..scheme.message_len())
+                .map(|_| randombits_i64(1) as i32)
+                .collect();
+            let decrypted = scheme
+                .encrypt(&pk, &msg)
+                .and_then(|ct| scheme.decrypt(&sk, &ct));
+            decrypted.as_deref() != Ok(&msg[..])
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Float;
+
+    use super::*;
+    use crate::{
+        error::Error,
+        lwe::{LweParameters, LweScheme},
+        module_lwe::ModuleEncryptionScheme,
+        ntru_prime::{NtruPrime, NtruPrimeParameters},
+        sampling::{Sampling, SecretDistribution},
+        util::{test_util::*, try_randombits_u64},
+    };
+
+    #[test]
+    fn test_decryption_failures() {
+        assert_eq!(decryption_failures(&scheme(), 20), 0);
+
+        let precision = 256;
+        let params = LweParameters {
+            n: 16,
+            m: 32,
+            l: 8,
+            q: Q,
+        };
+        let lwe = LweScheme::new(
+            params,
+            precision,
+            13.2,
+            Float::with_val(precision, 2.0),
+            Float::with_val(precision, 0),
+        )
+        .unwrap();
+        assert_eq!(decryption_failures(&lwe, 20), 0);
+
+        let mes = ModuleEncryptionScheme::new(scheme(), 2);
+        assert_eq!(decryption_failures(&mes, 20), 0);
+    }
+
+    #[test]
+    fn test_secret_distributions() {
+        let weight = P as usize / 2;
+        for secret in [
+            SecretDistribution::Ternary(weight),
+            SecretDistribution::Binary,
+        ] {
+            let es = scheme().with_secret_distribution(secret);
+            assert!(es.noise_bound() < Q / 4);
+            assert!(es.noise_std_dev() < scheme().noise_std_dev());
+            assert_eq!(decryption_failures(&es, 20), 0);
+
+            let (_, sk) = es.generate_keys();
+            let r2: Vec<i64> = (0..P as usize)
+                .map(|i| sk.r2.coeff(i).to_i64().unwrap())
+                .collect();
+            match secret {
+                SecretDistribution::Ternary(_) => {
+                    assert!(r2.iter().all(|x| (-1..=1).contains(x)));
+                    assert_eq!(r2.iter().filter(|&&x| x != 0).count(), weight);
+                }
+                _ => assert!(r2.iter().all(|x| (0..=1).contains(x))),
+            }
+        }
+
+        let too_heavy = SecretDistribution::Ternary(P as usize + 1);
+        assert!(scheme().try_with_secret_distribution(too_heavy).is_err());
+    }
+
+    #[test]
+    fn test_malformed_inputs() {
+        let es = scheme();
+        let (pk, sk) = es.generate_keys();
+
+        let short = vec![0; P as usize - 1];
+        assert_eq!(
+            es.encrypt(&pk, &short),
+            Err(Error::MessageLength {
+                expected: P as usize,
+                found: P as usize - 1
+            })
+        );
+
+        let mut ct = es.encrypt(&pk, &random_message()).unwrap();
+        ct.c2[0] += Q;
+        assert!(matches!(
+            es.decrypt(&sk, &ct),
+            Err(Error::ModulusMismatch { .. })
+        ));
+        ct.c2[0] -= Q;
+        ct.c1[P as usize] = 1.into();
+        assert_eq!(
+            es.decrypt(&sk, &ct),
+            Err(Error::WrongDegree {
+                bound: P as i64,
+                found: P as i64
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_sampler() {
+        let precision = 256;
+        let sampler = |tailcut, sigma| {
+            Sampling::try_new(
+                precision,
+                tailcut,
+                Float::with_val(precision, sigma),
+                Float::with_val(precision, 0),
+            )
+        };
+        assert!(sampler(13.2, 2.0).is_ok());
+        assert!(matches!(sampler(13.2, -1.0), Err(Error::InvalidSampler(_))));
+        assert!(matches!(sampler(0.0, 2.0), Err(Error::InvalidSampler(_))));
+        assert!(matches!(sampler(13.2, 1e9), Err(Error::InvalidSampler(_))));
+        assert_eq!(
+            try_randombits_u64(65),
+            Err(Error::InvalidBits {
+                bits: 65,
+                min: 1,
+                max: 64
+            })
+        );
+    }
+
+    fn check_kem<S: Kem>(kem: &S) {
+        let (pk, sk) = kem.generate_keys();
+        for _ in 0..5 {
+            let (ct, key) = kem.encapsulate(&pk).unwrap();
+            assert_eq!(kem.decapsulate(&sk, &ct), Ok(key));
+        }
+    }
+
+    #[test]
+    fn test_kem() {
+        check_kem(&scheme());
+
+        let toy = NtruPrimeParameters {
+            p: 31,
+            q: 139,
+            w: 8,
+        };
+        check_kem(&NtruPrime::new(toy).unwrap());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::util::zzx::ZZX;
+
+/// Polynomial f defining the ring Z_q[x]/(f)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RingModulus {
+    /// x^p + 1
+    #[default]
+    Cyclotomic,
+    /// x^p - x - 1, the NTRU Prime ring
+    NtruPrime,
+}
+
+impl RingModulus {
+    /// f, of degree p
+    pub fn polynomial(&self, p: usize) -> ZZX {
+        let mut f = ZZX::new();
+        f.set_length(p + 1);
+        f.set_coeff(p, Some(1));
+        match self {
+            RingModulus::Cyclotomic => {
+                f.set_coeff(0, Some(1));
+            }
+            RingModulus::NtruPrime => {
+                f.set_coeff(1, Some(-1));
+                f.set_coeff(0, Some(-1));
+            }
+        }
+        f
+    }
+
+    /// a % f, using x^p = -1 or x^p = x + 1 instead of a generic division
+    pub fn reduce(&self, a: &ZZX, p: usize) -> ZZX {
+        let mut r = a.clone();
+        for k in (p as i64..=a.deg()).rev() {
+            let k = k as usize;
+            let t = r.coeff(k);
+            if t == 0 {
+                continue;
+            }
+            r[k] = 0.into();
+            match self {
+                RingModulus::Cyclotomic => {
+                    r[k - p] -= t;
+                }
+                RingModulus::NtruPrime => {
+                    r[k - p + 1] += &t;
+                    r[k - p] += t;
+                }
+            }
+        }
+        r.normalize();
+        r
+    }
+
+    /// Worst case ratio between the largest coefficient of a * b % f and that of a * b
+    /// over Z[x], for a, b of degree < p. Every coefficient of a product reduced
+    /// mod x^p - x - 1 collects up to 2p terms of the convolution instead of p.
+    pub fn expansion_factor(&self) -> u32 {
+        match self {
+            RingModulus::Cyclotomic => 1,
+            RingModulus::NtruPrime => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Float;
+
+    use super::*;
+    use crate::{
+        encryption_scheme::EncryptionScheme,
+        pke::decryption_failures,
+        util::{test_util::Q, zzx::rem},
+    };
+
+    #[test]
+    fn test_reduce() {
+        let p = 7;
+        let a = ZZX::new_with_vec(vec![3, -1, 4, 1, -5, 9, 2, -6, 5, 3, -5, 8, 9, 7]);
+
+        for modulus in [RingModulus::Cyclotomic, RingModulus::NtruPrime] {
+            let f = modulus.polynomial(p);
+            let mut expected = ZZX::new();
+            rem(&mut expected, &a, &f);
+            expected.normalize();
+
+            assert_eq!(modulus.reduce(&a, p), expected);
+        }
+    }
+
+    #[test]
+    fn test_ntru_prime_polynomial() {
+        let f = RingModulus::NtruPrime.polynomial(761);
+        assert_eq!(f.deg(), 761);
+        assert_eq!(f[761], 1);
+        assert_eq!(f[1], -1);
+        assert_eq!(f[0], -1);
+    }
+
+    /// The ring of ntrup761, with a q large enough that decryption cannot fail
+    #[test]
+    fn test_ntru_prime_encryption() {
+        let precision = 256;
+        let sigma = Float::with_val(precision, 2.0);
+        let center = Float::with_val(precision, 0);
+        let es = EncryptionScheme::new_with_modulus(
+            761,
+            Q,
+            precision,
+            13.2,
+            sigma,
+            center,
+            RingModulus::NtruPrime,
+        );
+        assert_eq!(es.f(), &RingModulus::NtruPrime.polynomial(761));
+        assert!(es.noise_bound() < Q / 4);
+
+        assert_eq!(decryption_failures(&es, 10), 0);
+    }
+}
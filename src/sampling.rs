@@ -4,12 +4,56 @@ use rug::{
     Float,
 };
 
-use crate::util::randombits_i64;
+use crate::{
+    error::{Error, Result},
+    util::{random_u64, randombits_i64, RandomBits},
+};
+
+/// Largest supported tailcut * sigma, as the probability matrix has precision * (bound + 1) entries
+const MAX_SAMPLE_BOUND: u32 = 1 << 16;
+
+/// Largest supported precision (in bits) of the probabilities
+pub const MAX_PRECISION: u32 = 4096;
+
+/// Levels of the DDG tree resolved at once by the lookup table of `sample_into`
+const LUT_BITS: u32 = 8;
+
+/// Outcome of the first levels of a DDG tree walk for given random bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Hit(u32),
+    Miss,
+    /// No leaf yet, with this distance after the table levels
+    Continue(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LutEntry {
+    /* random bits consumed */
+    used: u32,
+    step: Step,
+}
+
+// The probability matrix has one row per bit level of the probabilities and one
+// column per value |x| = bound - col, a nonzero |x| carrying the probability of
+// both signs. As in Roy, Vercauteren and Verbauwhede, "High precision discrete
+// gaussian sampling on FPGAs", each bit level is packed into u64 words (bit
+// col % 64 of word col / 64) and its hamming weight is stored, so that the DDG
+// tree walk skips a whole level with one comparison and only scans the words of
+// the level where it hits.
 
 #[derive(Debug, Clone)]
 pub struct Sampling {
-    p: Vec<Vec<i32>>,
-    begin: Vec<i32>,
+    /* precision levels of `words` words each */
+    p: Vec<u64>,
+    words: usize,
+    hamming: Vec<u32>,
+    /* round(tailcut * sigma) + 1 and the rounded center, cached for the walk */
+    cols: usize,
+    offset: i32,
+    /* walks over the first `lut_bits` levels, indexed by their random bits */
+    lut: Vec<LutEntry>,
+    lut_bits: u32,
     precision: u32,
     tailcut: f32,
     sigma: Float,
@@ -18,84 +62,309 @@ pub struct Sampling {
 
 impl Sampling {
     pub fn new(precision: u32, tailcut: f32, sigma: Float, center: Float) -> Self {
-        let mut sampling = Self {
-            p: vec![],
-            begin: vec![],
+        Self::try_new(precision, tailcut, sigma, center).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// `new`, or an error if the parameters do not describe a usable sampler
+    pub fn try_new(precision: u32, tailcut: f32, sigma: Float, center: Float) -> Result<Self> {
+        Self::check_parameters(precision, tailcut, &sigma, &center)?;
+
+        let mut sampling = Self::empty(precision, tailcut, sigma, center);
+        sampling.build_probability_matrix();
+        sampling.build_lut();
+        Ok(sampling)
+    }
+
+    /// A sampler with an all zero probability matrix
+    fn empty(precision: u32, tailcut: f32, sigma: Float, center: Float) -> Self {
+        let cols = (tailcut * sigma.to_f32()).round() as usize + 1;
+        let words = cols.div_ceil(64);
+        Self {
+            p: vec![0; precision as usize * words],
+            words,
+            hamming: vec![0; precision as usize],
+            cols,
+            offset: center.to_f32().round() as i32,
+            lut: vec![],
+            lut_bits: LUT_BITS.min(precision),
             precision,
             tailcut,
             sigma,
             c: center,
-        };
-        sampling.build_probability_matrix();
-        sampling
+        }
+    }
+
+    /// A sampler with a precomputed probability matrix of `precision` rows and
+    /// `cols` = round(tailcut * sigma) + 1 columns, given by `bit(row, col)`
+    pub(crate) fn from_matrix(
+        precision: u32,
+        tailcut: f32,
+        sigma: Float,
+        center: Float,
+        cols: usize,
+        bit: impl Fn(usize, usize) -> bool,
+    ) -> Result<Self> {
+        Self::check_parameters(precision, tailcut, &sigma, &center)?;
+
+        let mut sampling = Self::empty(precision, tailcut, sigma, center);
+        if cols != sampling.columns() {
+            return Err(Error::InvalidSampler("malformed probability matrix"));
+        }
+        for row in 0..precision as usize {
+            for col in 0..cols {
+                if bit(row, col) {
+                    sampling.set_bit(row, col);
+                }
+            }
+        }
+        sampling.build_lut();
+        Ok(sampling)
+    }
+
+    fn check_parameters(precision: u32, tailcut: f32, sigma: &Float, center: &Float) -> Result<()> {
+        if precision == 0 || precision > MAX_PRECISION {
+            return Err(Error::InvalidSampler(
+                "precision must be between 1 and MAX_PRECISION",
+            ));
+        }
+        if !sigma.is_finite() || *sigma <= 0 {
+            return Err(Error::InvalidSampler("sigma must be positive"));
+        }
+        if !tailcut.is_finite() || tailcut <= 0.0 {
+            return Err(Error::InvalidSampler("tailcut must be positive"));
+        }
+        // the table is symmetric around the center; `CenteredSampling` takes any real center
+        if !center.is_integer() {
+            return Err(Error::InvalidSampler("center must be an integer"));
+        }
+        // the matrix has bound + 1 columns
+        let bound = (tailcut * sigma.to_f32()).round();
+        if bound < 1.0 {
+            return Err(Error::InvalidSampler("tailcut * sigma must be at least 1"));
+        }
+        if bound > MAX_SAMPLE_BOUND as f32 {
+            return Err(Error::InvalidSampler("tailcut * sigma is too large"));
+        }
+        Ok(())
+    }
+
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    pub fn tailcut(&self) -> f32 {
+        self.tailcut
+    }
+
+    pub fn sigma(&self) -> &Float {
+        &self.sigma
+    }
+
+    pub fn center(&self) -> &Float {
+        &self.c
+    }
+
+    /// Number of columns of the probability matrix, round(tailcut * sigma) + 1
+    pub(crate) fn columns(&self) -> usize {
+        self.cols
+    }
+
+    /// Bit `row` of the probability of |x| = bound - col
+    pub(crate) fn bit(&self, row: usize, col: usize) -> bool {
+        (self.p[row * self.words + col / 64] >> (col % 64)) & 1 == 1
+    }
+
+    fn set_bit(&mut self, row: usize, col: usize) {
+        if !self.bit(row, col) {
+            self.p[row * self.words + col / 64] |= 1 << (col % 64);
+            self.hamming[row] += 1;
+        }
     }
 
     // Knuth-Yao algorithm to obtain a sample from the discrete gaussian
     pub fn knuth_yao(&self) -> i32 {
-        let bound = (self.tailcut * self.sigma.clone().to_f32()).round() as usize;
-        let center = self.c.to_f32().round() as i32;
-        let p_num_rows = self.p.len(); // precision
-        let p_num_cols = self.p[0].len();
+        let (mut r, mut left) = (0u64, 0);
+        let col = self.walk(|| {
+            if left == 0 {
+                r = random_u64();
+                left = 64;
+            }
+            let bit = (r & 1) as u32;
+            r >>= 1;
+            left -= 1;
+            bit
+        });
+
+        let signal = 1 - 2 * randombits_i64(1) as i32; // Sample a random signal s
+        self.value(col, signal)
+    }
 
-        let mut random_bits: Vec<i32> = vec![0; p_num_rows];
+    /// `knuth_yao` with a walk whose running time and memory accesses do not depend on
+    /// the sample, as in the original cpp code: every level and every column of the
+    /// matrix is scanned, and the hit is recorded with masks instead of branches
+    pub fn knuth_yao_constant_time(&self) -> i32 {
+        let words: Vec<u64> = (0..self.precision.div_ceil(64))
+            .map(|_| random_u64())
+            .collect();
+        let mut level = 0;
+        let col = self.walk_constant_time(|| {
+            let bit = (words[level / 64] >> (level % 64)) & 1;
+            level += 1;
+            bit as u32
+        });
 
-        let length = 64; // sizeof(unsigned long)*8 // 64 bits
+        let signal = 1 - 2 * randombits_i64(1) as i32;
+        self.value(Some(col), signal)
+    }
 
-        let mut index = 0;
-        for _ in 0..(p_num_rows / length + 1) {
-            let mut r: u64 = rand::random::<u64>(); // RandomWord(); // It returns a word filled with pseudo-random bits
-            let mut j = 0;
-            while j < length && index < p_num_rows {
-                random_bits[index] = (r & 1) as i32; // getting the least significant bit
+    /// The walk of `walk` over all levels with no early exit, returning the column of
+    /// the leaf it hits, or 0 (a sample at the bound) for a miss
+    pub(crate) fn walk_constant_time(&self, mut random_bit: impl FnMut() -> u32) -> usize {
+        let cols = self.cols as i64;
 
-                j += 1;
-                index += 1;
-                r >>= 1;
+        let mut d: i64 = 0; // distance
+        let mut hit: u64 = 0; // all ones once a leaf is hit
+        let mut col_hit: u64 = 0;
+        for level in 0..self.precision as usize {
+            d = 2 * d + random_bit() as i64;
+            let words = &self.p[level * self.words..(level + 1) * self.words];
+            for col in 0..self.cols {
+                d -= ((words[col / 64] >> (col % 64)) & 1) as i64;
+                let enable = ((d == -1) as u64).wrapping_neg() & !hit;
+                col_hit |= col as u64 & enable;
+                hit |= enable;
             }
+            // keeps d bounded without changing the outcome: below 0 the leaf is
+            // already hit, and from `cols` on the tree is left without a hit
+            d = d.clamp(-1, cols);
         }
+        col_hit as usize
+    }
 
-        let mut d = 0; // distance
-        let invalid_sample = bound + 1;
-        let signal = 1 - 2 * randombits_i64(1) as i32; // Sample a random signal s
-        let mut hit = false;
+    /// The sample of a DDG tree walk ending in `col` (center ± bound for a miss)
+    pub(crate) fn value(&self, col: Option<usize>, signal: i32) -> i32 {
+        let bound = self.cols as i32 - 1;
+        (col.unwrap_or(0) as i32 - bound) * signal + self.offset
+    }
+
+    /// Fills `out` with samples x, |x - center| < bound, drawing the random bits of
+    /// all of them from one buffered stream. Draws outside the bound, including the
+    /// misses of the DDG tree, are rejected as the schemes do.
+    pub fn sample_into(&self, out: &mut [i32]) {
+        self.sample_into_with(out, &mut RandomBits::new());
+    }
+
+    /// `sample_into` reading the random bits from `bits`
+    pub fn sample_into_with(&self, out: &mut [i32], bits: &mut RandomBits) {
+        for x in out.iter_mut() {
+            *x = loop {
+                // a column of 0 is a sample of center ± bound
+                match self.walk_buffered(bits) {
+                    Some(col) if col > 0 => {
+                        let signal = 1 - 2 * bits.bit() as i32;
+                        break self.value(Some(col), signal);
+                    }
+                    _ => continue,
+                }
+            };
+        }
+    }
 
-        let mut s = 0;
-        for row in 0..p_num_rows {
-            d = 2 * d + random_bits[row]; // Distance calculus
-            for col in self.begin[row] as usize..p_num_cols {
-                d -= self.p[row][col];
+    /// Walks the DDG tree with one random bit per level, returning the column of the
+    /// leaf it hits, or `None` for a miss. The walk stops as soon as the outcome is
+    /// known, so its running time depends on the sample.
+    pub(crate) fn walk(&self, random_bit: impl FnMut() -> u32) -> Option<usize> {
+        match self.walk_from(0, 0, self.precision as usize, random_bit) {
+            Step::Hit(col) => Some(col as usize),
+            _ => None,
+        }
+    }
 
-                let enable = d == -1;
+    /// The walk over levels [start, end), from distance d
+    fn walk_from(
+        &self,
+        start: usize,
+        mut d: u32,
+        end: usize,
+        mut random_bit: impl FnMut() -> u32,
+    ) -> Step {
+        // every level has at most `cols` leaves, so a distance of `cols` or more
+        // only leads to internal nodes and the tree is left without a hit
+        let cols = self.cols as u32;
 
-                // when enable & !hit becomes 1, "col" is added to "S";
-                // e.g. enable = 1 and hit = 0
-                s += select(invalid_sample as i32, col as i32, enable & !hit);
-                hit |= enable & !hit;
+        for (level, &hamming) in self.hamming.iter().enumerate().take(end).skip(start) {
+            d = 2 * d + random_bit(); // distance
+            if d < hamming {
+                return Step::Hit(self.select(level, d) as u32);
+            }
+            d -= hamming;
+            if d >= cols {
+                return Step::Miss;
             }
         }
+        if end < self.precision as usize {
+            Step::Continue(d)
+        } else {
+            Step::Miss
+        }
+    }
 
-        // Note: the "col" value is in [0, bound]. So, the invalid sample must be greater than bound.
-        s %= invalid_sample as i32;
-        s -= bound as i32 + center;
-        s *= signal;
+    /// The walk for every value of the random bits of the first `lut_bits` levels
+    fn build_lut(&mut self) {
+        self.lut = (0..1u32 << self.lut_bits)
+            .map(|prefix| {
+                let mut used = 0;
+                let step = self.walk_from(0, 0, self.lut_bits as usize, || {
+                    used += 1;
+                    (prefix >> (used - 1)) & 1
+                });
+                LutEntry { used, step }
+            })
+            .collect();
+    }
+
+    /// `walk` resolving the first levels with the lookup table, and consuming only the
+    /// random bits of the levels it visits
+    fn walk_buffered(&self, bits: &mut RandomBits) -> Option<usize> {
+        let entry = self.lut[bits.peek(self.lut_bits) as usize];
+        bits.skip(entry.used);
+        let step = match entry.step {
+            Step::Continue(d) => {
+                self.walk_from(self.lut_bits as usize, d, self.precision as usize, || {
+                    bits.bit()
+                })
+            }
+            step => step,
+        };
+        match step {
+            Step::Hit(col) => Some(col as usize),
+            _ => None,
+        }
+    }
 
-        s
+    /// Column of the d-th (from 0) set bit of a level
+    fn select(&self, level: usize, mut d: u32) -> usize {
+        let words = &self.p[level * self.words..(level + 1) * self.words];
+        for (i, &word) in words.iter().enumerate() {
+            let ones = word.count_ones();
+            if d < ones {
+                let mut word = word;
+                for _ in 0..d {
+                    word &= word - 1; // clears the lowest set bit
+                }
+                return i * 64 + word.trailing_zeros() as usize;
+            }
+            d -= ones;
+        }
+        unreachable!("select: d is below the hamming weight of the level")
     }
 
     fn build_probability_matrix(&mut self) {
-        let mut aux_p: Vec<Vec<i32>> = vec![];
-        let mut aux_begin: Vec<i32> = vec![];
-
         // The random variable consists of elements in [c-tailcut*sigma, c+tailcut*sigma]
         let mut prob_of_x: Vec<Float> = vec![];
 
         let bound = (self.tailcut * self.sigma.clone().to_f32()).round() as usize;
         prob_of_x.resize_with(bound + 1, || Float::with_val(self.precision, 0));
-        aux_p.resize_with(self.precision as usize, Vec::new);
-
-        for aux_p_i in &mut aux_p {
-            aux_p_i.resize_with(bound + 1, || 0);
-        }
 
         for x in (1..=bound).rev() {
             prob_of_x[bound - x] = self.probability(
@@ -110,49 +379,39 @@ impl Sampling {
             self.sigma.clone(),
             self.c.clone(),
         );
-        prob_of_x[bound].div_assign_round(Float::with_val(self.precision, 2), Round::Nearest);
+
+        // a column |x| > 0 stands for both signs. Normalizing over [-bound, bound]
+        // leaves almost no probability outside the tree, so that the walk seldom
+        // misses; rounding down keeps the total at most 1.
+        let mut total = prob_of_x[bound].clone();
+        for prob in prob_of_x[..bound].iter_mut() {
+            *prob *= 2;
+            total += &*prob;
+        }
+        for prob in prob_of_x.iter_mut() {
+            prob.div_assign_round(&total, Round::Down);
+        }
 
         let mut i = -1;
-        for j in 0..self.precision as usize {
+        for row in 0..self.precision as usize {
             let pow: Float = Float::with_val(self.precision, 2).pow(i); // 2^{i}
             i -= 1;
             for x in (0..=bound).rev() {
-                aux_p[j][bound - x] = 0;
                 if prob_of_x[bound - x] >= pow.clone() {
-                    aux_p[j][bound - x] = 1;
+                    self.set_bit(row, bound - x);
                     prob_of_x[bound - x] -= pow.clone();
                 }
             }
         }
-
-        self.p = aux_p;
-
-        let p_num_cols = self.p[0].len();
-        let p_num_rows = self.p.len();
-
-        aux_begin.resize_with(p_num_rows, || 0);
-
-        // computing in which position the non-zero values in P start and end
-        for i in 0..p_num_rows {
-            aux_begin[i] = p_num_cols as i32 - 1;
-
-            for j in 0..p_num_cols {
-                if self.p[i][j] == 1 {
-                    aux_begin[i] = j as i32;
-                    break;
-                }
-            }
-        }
-
-        self.begin = aux_begin;
     }
 
-    fn probability(&self, x: Float, sigma: Float, c: Float) -> Float {
+    /// Density of the continuous gaussian of parameter sigma centered at c, evaluated at x
+    pub(crate) fn probability(&self, x: Float, sigma: Float, c: Float) -> Float {
         let pi = Float::with_val(self.precision, Constant::Pi);
         let s: Float = sigma.clone() * (Float::with_val(self.precision, 2) * pi).sqrt();
         let over_s: Float = 1 / s;
 
-        if x == 0 {
+        if x == c {
             return over_s;
         }
 
@@ -189,11 +448,656 @@ impl Sampling {
     // }
 }
 
-// bit = 0 then return a
-fn select(a: i32, b: i32, bit: bool) -> i32 {
-    if bit {
-        b
-    } else {
-        a
+/// Discrete gaussian over the integers with parameter sigma and a real center given
+/// per sample, as needed by trapdoor and signature samplers.
+///
+/// A sample y of the zero-centered Knuth-Yao sampler of parameter sigma * sqrt(2) is
+/// shifted to round(c) + y and accepted with probability exp(-(y - 2f)^2 / (4 sigma^2)),
+/// where f = c - round(c). The accepted values follow exp(-((y - f)^2 + f^2) / (2 sigma^2)),
+/// that is D_{Z, sigma, c}, and about 70% of the base samples are accepted.
+#[derive(Debug, Clone)]
+pub struct CenteredSampling {
+    base: Sampling,
+    tailcut: f32,
+    sigma: Float,
+}
+
+impl CenteredSampling {
+    pub fn new(precision: u32, tailcut: f32, sigma: Float) -> Self {
+        Self::try_new(precision, tailcut, sigma).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(precision: u32, tailcut: f32, sigma: Float) -> Result<Self> {
+        let base_sigma = sigma.clone() * Float::with_val(precision, 2).sqrt();
+        let base = Sampling::try_new(
+            precision,
+            tailcut,
+            base_sigma,
+            Float::with_val(precision, 0),
+        )?;
+
+        Ok(Self {
+            base,
+            tailcut,
+            sigma,
+        })
+    }
+
+    /// A sample x of D_{Z, sigma, center} with |x - center| < tailcut * sigma
+    pub fn sample(&self, center: &Float) -> i32 {
+        self.try_sample(center).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// `sample`, or an error if center is not finite or its samples may not fit an i32.
+    /// The acceptance test is computed at the precision of the base sampler.
+    pub fn try_sample(&self, center: &Float) -> Result<i32> {
+        let precision = self.base.precision;
+        let base_bound = (self.base.tailcut * self.base.sigma.to_f32()).round() as i32;
+        let bound = Float::with_val(precision, &self.sigma * self.tailcut);
+        let four_sigma2 = Float::with_val(precision, self.sigma.square_ref()) * 4u32;
+
+        let rounded = Float::with_val(precision, center.round_ref());
+        let f = Float::with_val(precision, center - &rounded);
+        let rounded = rounded
+            .to_integer()
+            .and_then(|rounded| rounded.to_i32())
+            .filter(|rounded| {
+                rounded.checked_add(base_bound).is_some()
+                    && rounded.checked_sub(base_bound).is_some()
+            })
+            .ok_or(Error::InvalidSampler(
+                "center must be finite and within the i32 range",
+            ))?;
+        let two_f = Float::with_val(precision, &f * 2u32);
+
+        loop {
+            let y = self.base.knuth_yao();
+            if y.abs() >= base_bound || Float::with_val(precision, y - &f).abs() >= bound {
+                continue;
+            }
+
+            // exp(-(y - 2f)^2 / (4 sigma^2))
+            let mut accept = Float::with_val(precision, y - &two_f);
+            accept.square_mut();
+            accept /= &four_sigma2;
+            accept = -accept;
+            accept.exp_mut();
+            if bernoulli(&accept) {
+                return Ok(rounded + y);
+            }
+        }
+    }
+}
+
+/// True with probability p: a uniform u in [0, 1) is compared to p 64 bits at a
+/// time, up to the precision of p, so that the draw is as precise as p itself
+pub(crate) fn bernoulli(p: &Float) -> bool {
+    if *p >= 1 {
+        return true;
+    }
+
+    let mut rest = p.clone();
+    for _ in 0..p.prec().div_ceil(64) {
+        rest <<= 64;
+        let digit = Float::with_val(rest.prec(), rest.floor_ref());
+        rest -= &digit;
+        let digit = digit.to_integer().and_then(|d| d.to_u64()).unwrap_or(0);
+
+        let u = random_u64();
+        if u != digit {
+            return u < digit;
+        }
+    }
+    false
+}
+
+/// Distribution of the secret of `EncryptionScheme`; the errors are always gaussian
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SecretDistribution {
+    /// The discrete gaussian of the errors
+    #[default]
+    Gaussian,
+    /// Exactly `weight` coefficients in {-1, 1} and all others 0
+    Ternary(usize),
+    /// Coefficients uniform in {0, 1}
+    Binary,
+}
+
+/// An integer uniform in [0, bound), for 0 < bound <= 2^31. Draws of
+/// ceil(log2 bound) bits are rejected until one is below bound, which takes
+/// fewer than two draws on average and has no modulo bias.
+fn uniform_below(bound: u32, bits: &mut RandomBits) -> u32 {
+    debug_assert!(bound > 0 && bound <= 1 << 31);
+    let width = u32::BITS - (bound - 1).leading_zeros();
+    loop {
+        let x = bits.take(width);
+        if x < bound {
+            return x;
+        }
+    }
+}
+
+/// Fills out with integers uniform in [0, q)
+pub fn sample_uniform_mod_q(out: &mut [i32], q: i32, bits: &mut RandomBits) {
+    try_sample_uniform_mod_q(out, q, bits).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// `sample_uniform_mod_q`, or an error if q is not positive
+pub fn try_sample_uniform_mod_q(out: &mut [i32], q: i32, bits: &mut RandomBits) -> Result<()> {
+    if q <= 0 {
+        return Err(Error::InvalidSampler("q must be positive"));
+    }
+    for x in out.iter_mut() {
+        *x = uniform_below(q as u32, bits) as i32;
+    }
+    Ok(())
+}
+
+/// Fills out with exactly `weight` coefficients in {-1, 1}, at uniformly chosen
+/// positions with uniform signs, and zeros
+pub fn sample_ternary(out: &mut [i32], weight: usize, bits: &mut RandomBits) {
+    try_sample_ternary(out, weight, bits).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// `sample_ternary`, or an error if weight exceeds the length of out
+pub fn try_sample_ternary(out: &mut [i32], weight: usize, bits: &mut RandomBits) -> Result<()> {
+    let n = out.len();
+    if weight > n {
+        return Err(Error::InvalidSampler(
+            "ternary weight exceeds the number of coefficients",
+        ));
+    }
+
+    // the first `weight` steps of a Fisher-Yates shuffle of the positions
+    let mut positions: Vec<usize> = (0..n).collect();
+    out.fill(0);
+    for i in 0..weight {
+        let j = i + uniform_below((n - i) as u32, bits) as usize;
+        positions.swap(i, j);
+        out[positions[i]] = 1 - 2 * bits.bit() as i32;
+    }
+    Ok(())
+}
+
+/// Fills out with coefficients uniform in {0, 1}
+pub fn sample_binary(out: &mut [i32], bits: &mut RandomBits) {
+    for x in out.iter_mut() {
+        *x = bits.bit() as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+
+    // Goodness of fit of `knuth_yao` against `probability`. A draw of center ± bound
+    // is a miss of the DDG tree or a sample at the bound, both of negligible
+    // probability, which callers reject, so the remaining samples are compared to
+    // the gaussian restricted to (center - bound, center + bound).
+    // `CenteredSampling` is compared to the gaussian restricted to its tail bound.
+    //
+    // The thresholds have a false positive rate of about 1e-5 each.
+
+    const PRECISION: u32 = 256;
+    const TAILCUT: f32 = 13.2;
+
+    /// Draws per sampler; `cargo test --release -- --ignored` repeats the checks
+    /// with `LARGE_SAMPLES`
+    const SAMPLES: usize = 20_000;
+    const LARGE_SAMPLES: usize = 4_000_000;
+
+    /* Normal quantile for a one-sided tail of 1e-5 */
+    const Z: f64 = 4.265;
+
+    fn sampler(sigma: f64, center: i32) -> Sampling {
+        Sampling::new(
+            PRECISION,
+            TAILCUT,
+            Float::with_val(PRECISION, sigma),
+            Float::with_val(PRECISION, center),
+        )
+    }
+
+    fn bound(gauss: &Sampling) -> i32 {
+        (gauss.tailcut * gauss.sigma.to_f32()).round() as i32
+    }
+
+    /// Counts of the draws in center + [-bound, bound], indexed by x - center + bound
+    fn draw(gauss: &Sampling, samples: usize) -> Vec<usize> {
+        let bound = bound(gauss);
+        let center = gauss.c.to_i32_saturating().unwrap();
+        let mut counts = vec![0; 2 * bound as usize + 1];
+        for _ in 0..samples {
+            let x = gauss.knuth_yao() - center;
+            assert!(
+                x.abs() <= bound,
+                "{} is outside the tail bound {}",
+                x,
+                bound
+            );
+            counts[(x + bound) as usize] += 1;
+        }
+        counts
+    }
+
+    /// Gaussian of parameter sigma centered at c, restricted to `support`
+    fn ideal(gauss: &Sampling, sigma: &Float, c: &Float, support: Range<i32>) -> Vec<Float> {
+        let density: Vec<Float> = support
+            .map(|x| gauss.probability(Float::with_val(PRECISION, x), sigma.clone(), c.clone()))
+            .collect();
+        let total = Float::with_val(PRECISION, Float::sum(density.iter()));
+        density.into_iter().map(|p| p / &total).collect()
+    }
+
+    fn check_distribution(sigma: f64, center: i32, samples: usize) {
+        let gauss = sampler(sigma, center);
+        let counts = draw(&gauss, samples);
+        let bound = bound(&gauss) as usize;
+
+        let misses = counts[0] + counts[2 * bound];
+        assert_eq!(misses, 0, "{} misses in {} draws", misses, samples);
+
+        let counts = &counts[1..2 * bound];
+        let b = bound as i32;
+        let zero = Float::with_val(PRECISION, 0);
+        let ideal: Vec<f64> = ideal(&gauss, &gauss.sigma, &zero, 1 - b..b)
+            .iter()
+            .map(Float::to_f64)
+            .collect();
+        check_fit(counts, &ideal);
+
+        // symmetry of every |x| > 0
+        for x in 1..bound {
+            let (negative, positive) = (counts[bound - 1 - x], counts[bound - 1 + x]);
+            let total = (negative + positive) as f64;
+            assert!(
+                (positive as f64 - total / 2.0).abs() <= Z * total.sqrt() / 2.0 + 1.0,
+                "{} draws of -{} and {} of {}",
+                negative,
+                x,
+                positive,
+                x
+            );
+        }
+    }
+
+    /// Chi-squared, Kolmogorov-Smirnov and statistical distance tests of the counts
+    /// against the probabilities `ideal`
+    fn check_fit(counts: &[usize], ideal: &[f64]) {
+        let n: usize = counts.iter().sum();
+        let nf = n as f64;
+
+        // chi-squared, pooling the bins below 5 expected samples into the tails
+        let (mut chi2, mut bins) = (0.0, 0);
+        let (mut tail_observed, mut tail_expected) = (0.0, 0.0);
+        for (&observed, &p) in counts.iter().zip(ideal.iter()) {
+            let expected = p * nf;
+            if expected < 5.0 {
+                tail_observed += observed as f64;
+                tail_expected += expected;
+            } else {
+                chi2 += (observed as f64 - expected).powi(2) / expected;
+                bins += 1;
+            }
+        }
+        if tail_expected > 0.0 {
+            chi2 += (tail_observed - tail_expected).powi(2) / tail_expected;
+            bins += 1;
+        }
+        // Wilson-Hilferty approximation of the chi-squared quantile
+        let df = (bins - 1) as f64;
+        let critical = df * (1.0 - 2.0 / (9.0 * df) + Z * (2.0 / (9.0 * df)).sqrt()).powi(3);
+        assert!(
+            chi2 < critical,
+            "chi2 = {} >= {} (df = {})",
+            chi2,
+            critical,
+            df
+        );
+
+        // Kolmogorov-Smirnov, conservative for a discrete distribution
+        let (mut observed_cdf, mut ideal_cdf, mut ks) = (0.0, 0.0, 0.0f64);
+        for (&observed, &p) in counts.iter().zip(ideal.iter()) {
+            observed_cdf += observed as f64 / nf;
+            ideal_cdf += p;
+            ks = ks.max((observed_cdf - ideal_cdf).abs());
+        }
+        let critical = (0.5 * (2.0 / 1e-5f64).ln()).sqrt() / nf.sqrt();
+        assert!(ks < critical, "KS statistic {} >= {}", ks, critical);
+
+        // statistical distance, against three times its expectation
+        // sum sqrt(2 p (1 - p) / (pi n)) / 2
+        let distance: f64 = counts
+            .iter()
+            .zip(ideal.iter())
+            .map(|(&observed, &p)| (observed as f64 / nf - p).abs())
+            .sum::<f64>()
+            / 2.0;
+        let expected: f64 = ideal
+            .iter()
+            .map(|&p| (2.0 * p * (1.0 - p) / (std::f64::consts::PI * nf)).sqrt())
+            .sum::<f64>()
+            / 2.0;
+        assert!(
+            distance < 3.0 * expected,
+            "statistical distance {} >= 3 * {}",
+            distance,
+            expected
+        );
+    }
+
+    fn check_centered(sigma: f64, center: f64, samples: usize) {
+        let gauss = CenteredSampling::new(PRECISION, TAILCUT, Float::with_val(PRECISION, sigma));
+        let c = Float::with_val(PRECISION, center);
+
+        // integers x with |x - c| < tailcut * sigma
+        let bound = (TAILCUT * sigma as f32) as f64;
+        let support = (center - bound).floor() as i32 + 1..(center + bound).ceil() as i32;
+        let mut counts = vec![0; support.len()];
+        for _ in 0..samples {
+            let x = gauss.sample(&c);
+            assert!(
+                support.contains(&x),
+                "{} is outside the tail bound around {}",
+                x,
+                center
+            );
+            counts[(x - support.start) as usize] += 1;
+        }
+
+        let ideal: Vec<f64> = ideal(&gauss.base, &gauss.sigma, &c, support)
+            .iter()
+            .map(Float::to_f64)
+            .collect();
+        check_fit(&counts, &ideal);
+    }
+
+    #[test]
+    fn test_knuth_yao_distribution() {
+        check_distribution(2.0, 0, SAMPLES);
+    }
+
+    #[test]
+    fn test_sample_into() {
+        for (sigma, center) in [(2.0, 0), (3.3, -7)] {
+            let gauss = sampler(sigma, center);
+            let b = bound(&gauss);
+
+            let mut samples = vec![0; SAMPLES / 2];
+            gauss.sample_into(&mut samples);
+            let mut counts = vec![0; 2 * b as usize - 1];
+            for x in samples {
+                assert!((x - center).abs() < b, "{} is outside the tail bound", x);
+                counts[(x - center + b - 1) as usize] += 1;
+            }
+
+            let zero = Float::with_val(PRECISION, 0);
+            let ideal: Vec<f64> = ideal(&gauss, &gauss.sigma, &zero, 1 - b..b)
+                .iter()
+                .map(Float::to_f64)
+                .collect();
+            check_fit(&counts, &ideal);
+        }
+    }
+
+    #[test]
+    fn test_knuth_yao_integer_center() {
+        check_distribution(2.0, 3, SAMPLES);
+        check_distribution(1.7, -5, SAMPLES);
+    }
+
+    #[test]
+    fn test_non_integer_center() {
+        let sampler = |center: f64| {
+            Sampling::try_new(
+                PRECISION,
+                TAILCUT,
+                Float::with_val(PRECISION, 2.0),
+                Float::with_val(PRECISION, center),
+            )
+        };
+        assert!(sampler(-4.0).is_ok());
+        assert!(matches!(sampler(0.5), Err(Error::InvalidSampler(_))));
+        assert!(matches!(sampler(f64::NAN), Err(Error::InvalidSampler(_))));
+    }
+
+    #[test]
+    fn test_centered_sampling() {
+        for (sigma, center) in [(2.0, 0.3), (1.7, -2.75), (3.3, 100.5)] {
+            check_centered(sigma, center, SAMPLES);
+        }
+
+        let gauss = CenteredSampling::new(PRECISION, TAILCUT, Float::with_val(PRECISION, 2.0));
+        for center in [f64::NAN, f64::INFINITY, 1e12, i32::MAX as f64] {
+            assert!(matches!(
+                gauss.try_sample(&Float::with_val(PRECISION, center)),
+                Err(Error::InvalidSampler(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_bernoulli() {
+        assert!(bernoulli(&Float::with_val(PRECISION, 1)));
+        assert!(!bernoulli(&Float::with_val(PRECISION, 0)));
+
+        // 2^-64 + 2^-200 needs more than the first word of u
+        let p = Float::with_val(PRECISION, 2).pow(-64) + Float::with_val(PRECISION, 2).pow(-200);
+        assert!((0..100).all(|_| !bernoulli(&p)));
+
+        let p = Float::with_val(PRECISION, 0.3);
+        let hits = (0..SAMPLES).filter(|_| bernoulli(&p)).count() as f64;
+        let expected = 0.3 * SAMPLES as f64;
+        assert!((hits - expected).abs() < Z * (expected * 0.7).sqrt());
+    }
+
+    #[test]
+    fn test_knuth_yao_non_integer_sigma() {
+        for sigma in [1.7, 3.3] {
+            // the tail bound is rounded to the nearest integer
+            assert_eq!(
+                bound(&sampler(sigma, 0)),
+                (TAILCUT * sigma as f32).round() as i32
+            );
+            check_distribution(sigma, 0, SAMPLES);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_knuth_yao_distribution_large() {
+        for sigma in [2.0, 1.7, 3.3, 3.19] {
+            check_distribution(sigma, 0, LARGE_SAMPLES);
+        }
+        for (sigma, center) in [(2.0, 0.3), (1.7, -2.75), (3.3, 1.0 / 3.0)] {
+            check_centered(sigma, center, LARGE_SAMPLES);
+        }
+    }
+
+    /// Scan of every column of every level, as in the original implementation
+    fn reference_walk(gauss: &Sampling, bits: &[u32]) -> Option<usize> {
+        let mut d: i64 = 0;
+        for (row, &bit) in bits.iter().enumerate() {
+            d = d.saturating_mul(2).saturating_add(bit as i64);
+            for col in 0..gauss.columns() {
+                d -= gauss.bit(row, col) as i64;
+                if d == -1 {
+                    return Some(col);
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_walk() {
+        for sigma in [2.0, 3.19, 60.0] {
+            let gauss = sampler(sigma, 0);
+            for _ in 0..2000 {
+                let bits: Vec<u32> = (0..PRECISION).map(|_| rand::random_range(0..2)).collect();
+                let mut stream = bits.iter();
+                let col = gauss.walk(|| *stream.next().unwrap());
+                assert_eq!(col, reference_walk(&gauss, &bits));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lut() {
+        for sigma in [2.0, 3.19, 60.0] {
+            let gauss = sampler(sigma, 0);
+            for _ in 0..2000 {
+                let bits: Vec<u32> = (0..PRECISION).map(|_| rand::random_range(0..2)).collect();
+                let prefix = (0..gauss.lut_bits as usize).fold(0, |acc, i| acc | (bits[i] << i));
+
+                let entry = gauss.lut[prefix as usize];
+                let mut rest = bits[entry.used as usize..].iter();
+                let step = match entry.step {
+                    Step::Continue(d) => {
+                        gauss.walk_from(gauss.lut_bits as usize, d, PRECISION as usize, || {
+                            *rest.next().unwrap()
+                        })
+                    }
+                    step => step,
+                };
+                let col = match step {
+                    Step::Hit(col) => Some(col as usize),
+                    _ => None,
+                };
+
+                let mut stream = bits.iter();
+                assert_eq!(col, gauss.walk(|| *stream.next().unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_walk_constant_time() {
+        // the scan of a wide matrix is slow in debug builds
+        for (sigma, walks) in [(2.0, 2000), (3.19, 2000), (60.0, 100)] {
+            let gauss = sampler(sigma, 0);
+            for _ in 0..walks {
+                let bits: Vec<u32> = (0..PRECISION).map(|_| rand::random_range(0..2)).collect();
+
+                let mut stream = bits.iter();
+                let col = gauss.walk_constant_time(|| *stream.next().unwrap());
+                // every level draws its bit
+                assert!(stream.next().is_none());
+
+                let mut stream = bits.iter();
+                assert_eq!(col, gauss.walk(|| *stream.next().unwrap()).unwrap_or(0));
+            }
+        }
+
+        let gauss = sampler(2.0, 3);
+        let b = bound(&gauss);
+        for _ in 0..1000 {
+            assert!((gauss.knuth_yao_constant_time() - 3).abs() <= b);
+        }
+    }
+
+    /// The distribution the probability matrix encodes is within 2^-precision per
+    /// entry of the gaussian, so its statistical and Renyi distances are negligible
+    #[test]
+    fn test_probability_matrix() {
+        for sigma in [2.0, 1.7, 3.3] {
+            let gauss = sampler(sigma, 0);
+            let bound = bound(&gauss) as usize;
+
+            // column col holds |x| = bound - col, both signs of a nonzero |x| together
+            let mut encoded: Vec<Float> = (0..=bound)
+                .map(|col| {
+                    let mut p = Float::with_val(PRECISION, 0);
+                    for row in 0..PRECISION as usize {
+                        if gauss.bit(row, col) {
+                            p += Float::with_val(PRECISION, 2).pow(-(row as i32) - 1);
+                        }
+                    }
+                    p
+                })
+                .collect();
+            encoded.reverse();
+            // P(x) for x in (-bound, bound)
+            let implied: Vec<Float> = (1 - bound as i32..bound as i32)
+                .map(|x| {
+                    let p = encoded[x.unsigned_abs() as usize].clone();
+                    if x == 0 {
+                        p
+                    } else {
+                        p / 2
+                    }
+                })
+                .collect();
+            let total = Float::with_val(PRECISION, Float::sum(implied.iter()));
+            let implied: Vec<Float> = implied.into_iter().map(|p| p / &total).collect();
+            let b = bound as i32;
+            let zero = Float::with_val(PRECISION, 0);
+            let ideal = ideal(&gauss, &gauss.sigma, &zero, 1 - b..b);
+
+            let tolerance = Float::with_val(PRECISION, 2).pow(-(PRECISION as i32) + 16);
+            let mut distance = Float::with_val(PRECISION, 0);
+            // Renyi divergence of order 2 is sum q^2 / p
+            let mut renyi = Float::with_val(PRECISION, 0);
+            for (q, p) in implied.iter().zip(ideal.iter()) {
+                distance += Float::with_val(PRECISION, q - p).abs() / 2;
+                renyi += Float::with_val(PRECISION, q * q) / p;
+            }
+            assert!(distance < tolerance, "statistical distance {}", distance);
+            assert!(renyi - 1u32 < tolerance, "Renyi divergence too large");
+        }
+    }
+
+    #[test]
+    fn test_uniform_mod_q() {
+        let mut bits = RandomBits::new();
+        for q in [1, 5, 1000] {
+            let mut samples = vec![0; SAMPLES];
+            sample_uniform_mod_q(&mut samples, q, &mut bits);
+
+            let mut counts = vec![0; q as usize];
+            for &x in &samples {
+                assert!((0..q).contains(&x), "{} is not in [0, {})", x, q);
+                counts[x as usize] += 1;
+            }
+            if q > 1 {
+                check_fit(&counts, &vec![1.0 / q as f64; q as usize]);
+            }
+        }
+        for q in [0, -5] {
+            assert!(try_sample_uniform_mod_q(&mut [0; 4], q, &mut bits).is_err());
+        }
+    }
+
+    #[test]
+    fn test_ternary_binary() {
+        let mut bits = RandomBits::new();
+        let (n, weight) = (50, 12);
+        let mut poly = vec![0; n];
+        // nonzero coefficients at each position, and coefficients equal to 1
+        let (mut positions, mut ones) = (vec![0; n], 0);
+        for _ in 0..SAMPLES / weight {
+            sample_ternary(&mut poly, weight, &mut bits);
+            assert_eq!(poly.iter().filter(|&&x| x != 0).count(), weight);
+            for (count, &x) in positions.iter_mut().zip(poly.iter()) {
+                assert!((-1..=1).contains(&x));
+                *count += (x != 0) as usize;
+                ones += (x == 1) as usize;
+            }
+        }
+        check_fit(&positions, &vec![1.0 / n as f64; n]);
+        let total = positions.iter().sum::<usize>();
+        check_fit(&[ones, total - ones], &[0.5, 0.5]);
+
+        sample_ternary(&mut poly, n, &mut bits);
+        assert!(poly.iter().all(|&x| x.abs() == 1));
+        assert!(try_sample_ternary(&mut poly, n + 1, &mut bits).is_err());
+
+        let mut samples = vec![0; SAMPLES];
+        sample_binary(&mut samples, &mut bits);
+        let ones = samples.iter().filter(|&&x| x == 1).count();
+        assert!(samples.iter().all(|&x| x == 0 || x == 1));
+        check_fit(&[ones, SAMPLES - ones], &[0.5, 0.5]);
     }
 }
@@ -0,0 +1,165 @@
+use rug::Float;
+use sha3::{Digest, Sha3_256};
+
+use crate::{
+    error::{Error, Result},
+    sampling::{Sampling, MAX_PRECISION},
+    serialization::Reader,
+};
+
+// Compact binary form of a Knuth-Yao probability matrix, so a sampler can be
+// loaded without recomputing its probabilities. All integers are little endian:
+//
+//   "KYTB" | version u8 | precision u32 | tailcut f32 | sigma | center | rows u32 | cols u32
+//   | matrix bits, row by row, packed from the least significant bit | SHA3-256 of the above
+//
+// where sigma and center are a u16 length and the exact hexadecimal digits of the Float.
+
+pub const TABLE_MAGIC: &[u8; 4] = b"KYTB";
+pub const TABLE_VERSION: u8 = 1;
+
+const DIGEST_BYTES: usize = 32;
+
+impl Sampling {
+    pub fn to_table_bytes(&self) -> Vec<u8> {
+        let (rows, cols) = (self.precision() as usize, self.columns());
+
+        let mut out = TABLE_MAGIC.to_vec();
+        out.push(TABLE_VERSION);
+        out.extend_from_slice(&self.precision().to_le_bytes());
+        out.extend_from_slice(&self.tailcut().to_le_bytes());
+        for value in [self.sigma(), self.center()] {
+            let digits = value.to_string_radix(16, None);
+            out.extend_from_slice(&(digits.len() as u16).to_le_bytes());
+            out.extend_from_slice(digits.as_bytes());
+        }
+        out.extend_from_slice(&(rows as u32).to_le_bytes());
+        out.extend_from_slice(&(cols as u32).to_le_bytes());
+
+        let mut bits = vec![0u8; (rows * cols).div_ceil(8)];
+        for row in 0..rows {
+            for col in 0..cols {
+                let i = row * cols + col;
+                bits[i / 8] |= (self.bit(row, col) as u8) << (i % 8);
+            }
+        }
+        out.extend_from_slice(&bits);
+
+        let digest = Sha3_256::digest(&out);
+        out.extend_from_slice(&digest);
+        out
+    }
+
+    /// Loads a sampler written by `to_table_bytes`
+    pub fn from_table_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < DIGEST_BYTES {
+            return Err(Error::Format("truncated data"));
+        }
+        let (body, digest) = bytes.split_at(bytes.len() - DIGEST_BYTES);
+        if Sha3_256::digest(body).as_slice() != digest {
+            return Err(Error::Format("table checksum mismatch"));
+        }
+
+        let mut reader = Reader { bytes: body };
+        if reader.take(TABLE_MAGIC.len())? != TABLE_MAGIC {
+            return Err(Error::Format("not a sampler table"));
+        }
+        if reader.byte()? != TABLE_VERSION {
+            return Err(Error::Format("unsupported version"));
+        }
+        // the checksum is no MAC, so the precision is bounded before it sizes any float
+        let precision = read_u32(&mut reader)?;
+        if precision == 0 || precision > MAX_PRECISION {
+            return Err(Error::InvalidSampler(
+                "precision must be between 1 and MAX_PRECISION",
+            ));
+        }
+        let tailcut = f32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        let sigma = read_float(&mut reader, precision)?;
+        let center = read_float(&mut reader, precision)?;
+        let rows = read_u32(&mut reader)? as usize;
+        let cols = read_u32(&mut reader)? as usize;
+
+        let len = rows
+            .checked_mul(cols)
+            .ok_or(Error::Format("table too large"))?;
+        let bits = reader.take(len.div_ceil(8))?;
+        if !reader.bytes.is_empty() {
+            return Err(Error::Format("trailing data"));
+        }
+        if rows != precision as usize {
+            return Err(Error::InvalidSampler("malformed probability matrix"));
+        }
+
+        Sampling::from_matrix(precision, tailcut, sigma, center, cols, |row, col| {
+            let i = row * cols + col;
+            (bits[i / 8] >> (i % 8)) & 1 == 1
+        })
+    }
+}
+
+fn read_u32(reader: &mut Reader) -> Result<u32> {
+    Ok(u32::from_le_bytes(reader.take(4)?.try_into().unwrap()))
+}
+
+fn read_float(reader: &mut Reader, precision: u32) -> Result<Float> {
+    let len = u16::from_le_bytes(reader.take(2)?.try_into().unwrap()) as usize;
+    let digits = std::str::from_utf8(reader.take(len)?).map_err(|_| Error::Format("bad number"))?;
+    let value = Float::parse_radix(digits, 16).map_err(|_| Error::Format("bad number"))?;
+    Ok(Float::with_val(precision, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler(sigma: f64) -> Sampling {
+        let precision = 256;
+        Sampling::new(
+            precision,
+            13.2,
+            Float::with_val(precision, sigma),
+            Float::with_val(precision, 0),
+        )
+    }
+
+    #[test]
+    fn test_table_round_trip() {
+        for sigma in [2.0, 3.19] {
+            let gauss = sampler(sigma);
+            let bytes = gauss.to_table_bytes();
+            let loaded = Sampling::from_table_bytes(&bytes).unwrap();
+
+            for row in 0..gauss.precision() as usize {
+                for col in 0..gauss.columns() {
+                    assert_eq!(loaded.bit(row, col), gauss.bit(row, col));
+                }
+            }
+            assert_eq!(loaded.sigma(), gauss.sigma());
+            assert_eq!(loaded.to_table_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_malformed_table() {
+        let bytes = sampler(2.0).to_table_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[40] ^= 1;
+        assert_eq!(
+            Sampling::from_table_bytes(&corrupted).err(),
+            Some(Error::Format("table checksum mismatch"))
+        );
+        assert!(Sampling::from_table_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Sampling::from_table_bytes(b"").is_err());
+
+        // a huge precision behind a valid checksum is rejected before reading sigma
+        let mut body = bytes[..bytes.len() - DIGEST_BYTES].to_vec();
+        body[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        body.extend_from_slice(&Sha3_256::digest(&body));
+        assert!(matches!(
+            Sampling::from_table_bytes(&body),
+            Err(Error::InvalidSampler(_))
+        ));
+    }
+}
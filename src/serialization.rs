@@ -0,0 +1,200 @@
+use crate::{
+    encryption_scheme::{RlweCiphertext, RlwePublicKey, RlweSecretKey},
+    envelope::{Envelope, TAG_BYTES},
+    error::{Error, Result},
+    params::ParameterSet,
+    util::zzx::ZZX,
+};
+
+// Binary encoding of keys and envelopes:
+//
+//   magic "RLWE" | version (1 byte) | kind (1 byte) | name length (1 byte) | parameter set name
+//
+// followed by the payload, where every polynomial is p little-endian i32 coefficients:
+//
+//   public key: a | p1
+//   secret key: r2
+//   envelope:   c1 | c2 | body length (u64, little-endian) | body | tag
+
+pub const MAGIC: &[u8; 4] = b"RLWE";
+pub const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    PublicKey(RlwePublicKey),
+    SecretKey(RlweSecretKey),
+    Envelope(Envelope),
+}
+
+impl Object {
+    fn kind(&self) -> u8 {
+        match self {
+            Object::PublicKey(_) => 1,
+            Object::SecretKey(_) => 2,
+            Object::Envelope(_) => 3,
+        }
+    }
+
+    /// Human readable kind
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Object::PublicKey(_) => "public key",
+            Object::SecretKey(_) => "secret key",
+            Object::Envelope(_) => "envelope",
+        }
+    }
+}
+
+/// The p coefficients of a, little-endian
+pub(crate) fn encode_poly(out: &mut Vec<u8>, a: &ZZX, p: usize) -> Result<()> {
+    for i in 0..p {
+        let a_i = a
+            .coeff(i)
+            .to_i32()
+            .ok_or(Error::Format("coefficient does not fit in 32 bits"))?;
+        out.extend_from_slice(&a_i.to_le_bytes());
+    }
+    Ok(())
+}
+
+pub fn to_bytes(params: &ParameterSet, object: &Object) -> Result<Vec<u8>> {
+    let p = params.p as usize;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.push(object.kind());
+    out.push(params.name.len() as u8);
+    out.extend_from_slice(params.name.as_bytes());
+
+    match object {
+        Object::PublicKey(pk) => {
+            encode_poly(&mut out, &pk.a, p)?;
+            encode_poly(&mut out, &pk.p1, p)?;
+        }
+        Object::SecretKey(sk) => {
+            encode_poly(&mut out, &sk.r2, p)?;
+        }
+        Object::Envelope(envelope) => {
+            encode_poly(&mut out, &envelope.kem.c1, p)?;
+            encode_poly(&mut out, &envelope.kem.c2, p)?;
+            out.extend_from_slice(&(envelope.body.len() as u64).to_le_bytes());
+            out.extend_from_slice(&envelope.body);
+            out.extend_from_slice(&envelope.tag);
+        }
+    }
+    Ok(out)
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<(&'static ParameterSet, Object)> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(Error::Format("not an rlwe file"));
+    }
+    if reader.byte()? != VERSION {
+        return Err(Error::Format("unsupported version"));
+    }
+    let kind = reader.byte()?;
+    let name_len = reader.byte()? as usize;
+    let name = String::from_utf8_lossy(reader.take(name_len)?).into_owned();
+    let params = ParameterSet::by_name(&name).ok_or(Error::UnknownParameterSet(name))?;
+    let p = params.p as usize;
+
+    let object = match kind {
+        1 => Object::PublicKey(RlwePublicKey {
+            a: reader.poly(p)?,
+            p1: reader.poly(p)?,
+        }),
+        2 => Object::SecretKey(RlweSecretKey {
+            r2: reader.poly(p)?,
+        }),
+        3 => {
+            let kem = RlweCiphertext {
+                c1: reader.poly(p)?,
+                c2: reader.poly(p)?,
+            };
+            let body_len = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+            let body_len =
+                usize::try_from(body_len).map_err(|_| Error::Format("body too large"))?;
+            let body = reader.take(body_len)?.to_vec();
+            let tag = reader.take(TAG_BYTES)?.try_into().unwrap();
+            Object::Envelope(Envelope { kem, body, tag })
+        }
+        _ => return Err(Error::Format("unknown object kind")),
+    };
+
+    if !reader.bytes.is_empty() {
+        return Err(Error::Format("trailing data"));
+    }
+    Ok((params, object))
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            return Err(Error::Format("truncated data"));
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn poly(&mut self, p: usize) -> Result<ZZX> {
+        let coeffs: Vec<i32> = self
+            .take(4 * p)?
+            .chunks_exact(4)
+            .map(|c| i32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        Ok(ZZX::new_with_vec(coeffs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params::TOY, pke::KeyGen};
+
+    #[test]
+    fn test_round_trip() {
+        let es = TOY.scheme().unwrap();
+        let (pk, sk) = es.generate_keys();
+        let envelope = es.seal(&pk, b"attack at dawn").unwrap();
+
+        for object in [
+            Object::PublicKey(pk),
+            Object::SecretKey(sk),
+            Object::Envelope(envelope),
+        ] {
+            let bytes = to_bytes(&TOY, &object).unwrap();
+            let (params, decoded) = from_bytes(&bytes).unwrap();
+            assert_eq!(params, &TOY);
+            assert_eq!(decoded, object);
+
+            assert_eq!(
+                from_bytes(&bytes[..bytes.len() - 1]),
+                Err(Error::Format("truncated data"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        assert_eq!(
+            from_bytes(b"NOPE\x01\x01"),
+            Err(Error::Format("not an rlwe file"))
+        );
+        assert_eq!(
+            from_bytes(b"RLWE\x01\x01\x03foo"),
+            Err(Error::UnknownParameterSet("foo".into()))
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use rug::{ops::RemRounding, Complete, Integer};
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    util::zzx::ZZX,
+};
+
+// t-out-of-n threshold decryption: r2 is split with Shamir secret sharing over
+// R_q = Z_q[x]/(x^p + 1), share i being the evaluation at x = i of
+// S(X) = r2 + A_1 X + ... + A_{t-1} X^{t-1} with uniform A_k in R_q.
+//
+// Lagrange coefficients are applied to the partial decryptions. Their smudging
+// noise is scaled by n!, so that lambda_i * n! * e_i stays small for any subset.
+
+/// Share of the secret key held by key-holder `index` (1..=n)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecretShare {
+    pub index: usize,
+    pub s: ZZX,
+}
+
+/// c1 * s_i + n! * e_i, computed by key-holder `index`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialDecryption {
+    pub index: usize,
+    pub d: ZZX,
+}
+
+impl EncryptionScheme {
+    /// Evaluates sum_k poly[k] * x^k with coefficients in R_q (Horner's rule)
+    fn eval_share_poly(&self, poly: &[ZZX], x: usize) -> ZZX {
+        let x = ZZX::new_with_val(x as u64);
+
+        let mut acc = ZZX::new();
+        for c in poly.iter().rev() {
+            acc = &(&acc * &x) + c;
+            self._mod(&mut acc);
+        }
+        acc
+    }
+
+    /// lambda_i * n!, as an integer, for the evaluation at 0
+    fn scaled_lagrange_coefficient(&self, indices: &[usize], i: usize, n: usize) -> Integer {
+        let mut num = Integer::factorial(n as u32).complete();
+        let mut den = Integer::from(1);
+        for &j in indices.iter().filter(|&&j| j != i) {
+            num *= j;
+            den *= j as i64 - i as i64;
+        }
+        num.div_exact(&den)
+    }
+
+    /// lambda_i mod q, for the evaluation at 0, or `None` if n! is not invertible mod q
+    fn lagrange_coefficient(&self, indices: &[usize], i: usize, n: usize) -> Option<Integer> {
+        let q = Integer::from(self.q);
+        let delta_inv = Integer::factorial(n as u32).complete().invert(&q).ok()?;
+
+        let lambda = self.scaled_lagrange_coefficient(indices, i, n) * delta_inv;
+        Some(lambda.rem_euc(q))
+    }
+
+    /// Indices of key-holders, which must be distinct and in 1..=n
+    fn check_indices(&self, indices: &[usize], n: usize) -> Result<()> {
+        if indices.iter().any(|&i| i == 0 || i > n) {
+            return Err(Error::InvalidShares("index outside of 1..=n"));
+        }
+        let mut sorted = indices.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.len() != indices.len() {
+            return Err(Error::InvalidShares("duplicate index"));
+        }
+        Ok(())
+    }
+
+    /// Splits r2 into n shares, any t of which can decrypt. Fails with
+    /// `Error::InvalidThreshold` unless 1 <= t <= n, and with `Error::NotInvertible`
+    /// if q shares a factor with n!.
+    pub fn shamir_share(&self, r2: &ZZX, t: usize, n: usize) -> Result<Vec<SecretShare>> {
+        if t == 0 || t > n {
+            return Err(Error::InvalidThreshold { t, n });
+        }
+        if Integer::factorial(n as u32)
+            .complete()
+            .gcd(&Integer::from(self.q))
+            != 1
+        {
+            return Err(Error::NotInvertible);
+        }
+
+        let mut poly = vec![r2.clone()];
+        self._mod(&mut poly[0]);
+        for _ in 1..t {
+            poly.push(self.uniform_poly());
+        }
+
+        Ok((1..=n)
+            .map(|index| SecretShare {
+                index,
+                s: self.eval_share_poly(&poly, index),
+            })
+            .collect())
+    }
+
+    /// Checks that shares are reduced, have distinct indices, and lie on a
+    /// single sharing polynomial of degree < t. Needs more than t shares to
+    /// detect a corrupted one.
+    pub fn verify_shares(&self, shares: &[SecretShare], t: usize) -> bool {
+        if t == 0 || shares.len() < t {
+            return false;
+        }
+
+        let mut indices: Vec<usize> = shares.iter().map(|share| share.index).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.len() != shares.len() || indices[0] == 0 {
+            return false;
+        }
+
+        for share in shares {
+            if share.s.deg() >= self.p as i64 {
+                return false;
+            }
+            for i in 0..self.p as usize {
+                let c = share.s.coeff(i);
+                if c < 0 || c >= self.q {
+                    return false;
+                }
+            }
+        }
+
+        // interpolate from the first t shares and check the remaining ones
+        let base = &shares[..t];
+        let base_indices: Vec<usize> = base.iter().map(|share| share.index).collect();
+        let q = Integer::from(self.q);
+        for share in &shares[t..] {
+            let mut expected = ZZX::new();
+            for b in base {
+                // lambda_b(x) = prod_{j != b} (x - j) / (b - j) mod q
+                let mut num = Integer::from(1);
+                let mut den = Integer::from(1);
+                for &j in base_indices.iter().filter(|&&j| j != b.index) {
+                    num *= share.index as i64 - j as i64;
+                    den *= b.index as i64 - j as i64;
+                }
+                let den_inv = match den.invert(&q) {
+                    Ok(den_inv) => den_inv,
+                    Err(_) => return false,
+                };
+                let lambda = (num * den_inv).rem_euc(&q);
+                expected += &b.s * &ZZX::new_with_val(lambda);
+            }
+            self._mod(&mut expected);
+
+            if expected != share.s {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Partial decryption c1 * s_i + n! * e_i, with e_i uniform in
+    /// [-2^{smudging_bits}, 2^{smudging_bits}). Fails if smudging_bits is above
+    /// `MAX_SMUDGING_BITS`.
+    pub fn shamir_partial_decryption(
+        &self,
+        c1: &ZZX,
+        share: &SecretShare,
+        n: usize,
+        smudging_bits: u8,
+    ) -> Result<PartialDecryption> {
+        let delta = Integer::factorial(n as u32).complete();
+        let noise = &self.smudging_noise(smudging_bits)? * &ZZX::new_with_val(delta);
+
+        let mut d = ZZX::new();
+        self.decryption(&mut d, c1, &noise, &share.s);
+
+        Ok(PartialDecryption {
+            index: share.index,
+            d,
+        })
+    }
+
+    /// Combines at least t partial decryptions (out of n key-holders) and decodes the
+    /// result. Fails with `Error::InvalidShares` if there are none or their indices
+    /// repeat or fall outside of 1..=n, and with `Error::NotInvertible` if q shares a
+    /// factor with n!.
+    pub fn combine_shamir_partial_decryptions(
+        &self,
+        m: &mut [i32],
+        c2: &ZZX,
+        partials: &[PartialDecryption],
+        n: usize,
+    ) -> Result<()> {
+        if partials.is_empty() {
+            return Err(Error::InvalidShares("no partial decryptions"));
+        }
+        let indices: Vec<usize> = partials.iter().map(|partial| partial.index).collect();
+        self.check_indices(&indices, n)?;
+
+        let mut sum = c2.clone();
+        for partial in partials {
+            let lambda = self
+                .lagrange_coefficient(&indices, partial.index, n)
+                .ok_or(Error::NotInvertible)?;
+            sum += &partial.d * &ZZX::new_with_val(lambda);
+        }
+        self._mod(&mut sum);
+
+        self.decode(m, &sum);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Float;
+
+    use super::*;
+    use crate::util::test_util::*;
+
+    const T: usize = 3;
+    const N: usize = 5;
+    const SMUDGING_BITS: u8 = 8;
+
+    #[test]
+    fn test_verify_shares() {
+        let es = scheme();
+        let a = uniform_poly();
+        let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+        es.key_generation(&a, &mut r2, &mut p1);
+
+        let mut shares = es.shamir_share(&r2, T, N).unwrap();
+        assert!(es.verify_shares(&shares, T));
+
+        shares[4].s += ZZX::new_with_val(1);
+        assert!(!es.verify_shares(&shares, T));
+    }
+
+    #[test]
+    fn test_shamir_decryption() {
+        let es = scheme();
+        let a = uniform_poly();
+        let (mut r2, mut p1) = (ZZX::new(), ZZX::new());
+        es.key_generation(&a, &mut r2, &mut p1);
+
+        let shares = es.shamir_share(&r2, T, N).unwrap();
+
+        for subset in [[0, 1, 2], [1, 3, 4], [0, 2, 4]] {
+            check_round_trips(&es, &a, &p1, 3, |c1, c2| {
+                let partials: Vec<PartialDecryption> = subset
+                    .iter()
+                    .map(|&i| {
+                        es.shamir_partial_decryption(c1, &shares[i], N, SMUDGING_BITS)
+                            .unwrap()
+                    })
+                    .collect();
+
+                let mut mdecoded = vec![0; P as usize];
+                es.combine_shamir_partial_decryptions(&mut mdecoded, c2, &partials, N)
+                    .unwrap();
+                mdecoded
+            });
+        }
+    }
+
+    #[test]
+    fn test_shamir_errors() {
+        let es = scheme();
+        let share = SecretShare {
+            index: 1,
+            s: uniform_poly(),
+        };
+        assert!(matches!(
+            es.shamir_partial_decryption(&uniform_poly(), &share, N, u8::MAX),
+            Err(Error::InvalidBits { .. })
+        ));
+        for t in [0, N + 1] {
+            assert_eq!(
+                es.shamir_share(&share.s, t, N),
+                Err(Error::InvalidThreshold { t, n: N })
+            );
+        }
+
+        let partial = |index| PartialDecryption {
+            index,
+            d: ZZX::new(),
+        };
+        let mut m = vec![0; P as usize];
+        for (indices, reason) in [
+            (vec![], "no partial decryptions"),
+            (vec![1, 2, 2], "duplicate index"),
+            (vec![0, 1, 2], "index outside of 1..=n"),
+            (vec![1, 2, N + 1], "index outside of 1..=n"),
+        ] {
+            let partials: Vec<PartialDecryption> = indices.into_iter().map(partial).collect();
+            assert_eq!(
+                es.combine_shamir_partial_decryptions(&mut m, &ZZX::new(), &partials, N),
+                Err(Error::InvalidShares(reason))
+            );
+        }
+
+        // 5! = 120 is not invertible mod 1050
+        let sigma = Float::with_val(256, 2.0);
+        let es = EncryptionScheme::new(P, 1050, 256, 13.2, sigma, Float::with_val(256, 0));
+        assert_eq!(es.lagrange_coefficient(&[1, 2, 3], 1, N), None);
+        assert_eq!(
+            es.combine_shamir_partial_decryptions(&mut m, &ZZX::new(), &[partial(1)], N),
+            Err(Error::NotInvertible)
+        );
+        assert_eq!(es.shamir_share(&share.s, T, N), Err(Error::NotInvertible));
+    }
+}
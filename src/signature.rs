@@ -0,0 +1,260 @@
+use rug::Float;
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    sampling::{bernoulli, Sampling},
+    util::zzx::ZZX,
+};
+
+// Ring-LWE signature following Lyubashevsky's Fiat-Shamir with aborts
+// (https://eprint.iacr.org/2011/537.pdf), over the ring of an `EncryptionScheme`.
+//
+// The signing key (s1, s2) is sampled like any other secret of the scheme and
+// t = a * s1 + s2. A signature is (z1, z2, c) with z = y + s * c for gaussian
+// masks y, c being a sparse challenge hashed from a * y1 + y2 and the message.
+// Rejection sampling makes z independent of the secret.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub z1: ZZX,
+    pub z2: ZZX,
+    pub c: ZZX,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignatureScheme {
+    es: EncryptionScheme,
+
+    /* Number of non-zero coefficients of a challenge */
+    kappa: usize,
+
+    /* Masking distribution and repetition rate */
+    sigma: Float,
+    m: Float,
+
+    gauss: Sampling,
+}
+
+impl SignatureScheme {
+    fn mask_sampling(&self, y: &mut ZZX) {
+        let mut samples = vec![0; self.es.p as usize];
+        self.gauss.sample_into(&mut samples);
+
+        y.set_length(self.es.p as usize);
+        for (i, sample) in samples.into_iter().enumerate() {
+            y.set_coeff(i, Some(sample));
+        }
+    }
+
+    /// c = H(w, msg), a polynomial with kappa coefficients in {-1, 1} and all others 0
+    fn hash_to_challenge(&self, w: &ZZX, msg: &[u8]) -> ZZX {
+        let p = self.es.p as usize;
+
+        let mut hasher = Shake256::default();
+        for i in 0..p {
+            hasher.update(&w.coeff(i).to_u32().unwrap().to_le_bytes());
+        }
+        hasher.update(msg);
+        let mut reader = hasher.finalize_xof();
+
+        let mut buf = [0u8; 8];
+        reader.read(&mut buf);
+        let mut signs = u64::from_le_bytes(buf);
+
+        // Fisher-Yates shuffle of kappa non-zero entries into position
+        let mask = p.next_power_of_two() - 1;
+        let mut c = vec![0i32; p];
+        for i in (p - self.kappa)..p {
+            let mut j;
+            loop {
+                let mut buf = [0u8; 2];
+                reader.read(&mut buf);
+                j = u16::from_le_bytes(buf) as usize & mask;
+                if j <= i {
+                    break;
+                }
+            }
+            c[i] = c[j];
+            c[j] = 1 - 2 * (signs & 1) as i32;
+            signs >>= 1;
+        }
+
+        ZZX::new_with_vec(c)
+    }
+
+    /// D_sigma(z) / (M * D_{v, sigma}(z)), the probability of outputting z = y + v
+    fn acceptance_probability(&self, z: &[&ZZX], v: &[&ZZX]) -> Float {
+        let precision = self.sigma.prec();
+        let zero = Float::with_val(precision, 0);
+
+        let mut ratio = Float::with_val(precision, 1) / self.m.clone();
+        for (z_j, v_j) in z.iter().zip(v.iter()) {
+            for i in 0..self.es.p as usize {
+                let x = Float::with_val(precision, z_j.coeff(i));
+                let center = Float::with_val(precision, v_j.coeff(i));
+                ratio *= self
+                    .gauss
+                    .probability(x.clone(), self.sigma.clone(), zero.clone());
+                ratio /= self.gauss.probability(x, self.sigma.clone(), center);
+            }
+        }
+        ratio
+    }
+
+    /// Upper bound on the euclidean norm of (z1, z2): 2 * sigma * sqrt(2p)
+    fn norm_bound(&self) -> Float {
+        let dimension = Float::with_val(self.sigma.prec(), 2 * self.es.p);
+        2 * self.sigma.clone() * dimension.sqrt()
+    }
+}
+
+impl SignatureScheme {
+    /// `sigma` is the standard deviation of the masks y and `m` the expected number of
+    /// signing attempts. Lyubashevsky suggests sigma = 12 * ||s * c|| with m = e^{1 + 1/288}.
+    /// Fails unless 1 <= kappa <= min(64, p) and sigma is usable by the sampler.
+    pub fn new(es: EncryptionScheme, kappa: usize, sigma: Float, m: Float) -> Result<Self> {
+        if kappa == 0 || kappa > 64 || kappa > es.p as usize {
+            return Err(Error::InvalidParameters(
+                "kappa must be between 1 and min(64, p)",
+            ));
+        }
+
+        let precision = sigma.prec();
+        let gauss = Sampling::try_new(
+            precision,
+            es.tailcut,
+            sigma.clone(),
+            Float::with_val(precision, 0),
+        )?;
+
+        Ok(Self {
+            es,
+            kappa,
+            sigma,
+            m,
+            gauss,
+        })
+    }
+
+    pub fn key_generation(&self, a: &ZZX, s1: &mut ZZX, s2: &mut ZZX, t: &mut ZZX) {
+        self.es.poly_sampling(s1);
+        self.es.poly_sampling(s2);
+
+        *t = self.es.mulmod(a, s1) + &*s2;
+
+        self.es._mod(t);
+    }
+
+    pub fn sign(&self, msg: &[u8], a: &ZZX, s1: &ZZX, s2: &ZZX) -> Signature {
+        loop {
+            let mut y1 = ZZX::new();
+            let mut y2 = ZZX::new();
+            self.mask_sampling(&mut y1);
+            self.mask_sampling(&mut y2);
+
+            let mut w = self.es.mulmod(a, &y1) + &y2;
+            self.es._mod(&mut w);
+
+            let c = self.hash_to_challenge(&w, msg);
+
+            let v1 = self.es.mulmod(s1, &c);
+            let v2 = self.es.mulmod(s2, &c);
+            let z1 = y1 + &v1;
+            let z2 = y2 + &v2;
+
+            if bernoulli(&self.acceptance_probability(&[&z1, &z2], &[&v1, &v2])) {
+                return Signature { z1, z2, c };
+            }
+        }
+    }
+
+    pub fn verify(&self, msg: &[u8], sig: &Signature, a: &ZZX, t: &ZZX) -> bool {
+        let p = self.es.p as i64;
+        if sig.z1.deg() >= p || sig.z2.deg() >= p || sig.c.deg() >= p {
+            return false;
+        }
+
+        let mut norm = Float::with_val(self.sigma.prec(), 0);
+        for i in 0..p as usize {
+            norm += sig.z1.coeff(i).square();
+            norm += sig.z2.coeff(i).square();
+        }
+        if norm.sqrt() > self.norm_bound() {
+            return false;
+        }
+
+        // a * z1 + z2 - t * c = a * y1 + y2
+        let mut w = self.es.mulmod(a, &sig.z1) + &sig.z2 - self.es.mulmod(t, &sig.c);
+        self.es._mod(&mut w);
+
+        self.hash_to_challenge(&w, msg) == sig.c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::*;
+
+    const KAPPA: usize = 4;
+
+    fn signature_scheme() -> SignatureScheme {
+        let precision = 256;
+        let sigma = Float::with_val(precision, 250);
+        let m = Float::with_val(precision, 1.0 + 1.0 / 288.0).exp();
+        SignatureScheme::new(scheme(), KAPPA, sigma, m).unwrap()
+    }
+
+    #[test]
+    fn test_hash_to_challenge() {
+        let ss = signature_scheme();
+        let w = uniform_poly();
+
+        let c = ss.hash_to_challenge(&w, b"message");
+        assert_eq!(c, ss.hash_to_challenge(&w, b"message"));
+
+        let mut weight = 0;
+        for i in 0..P as usize {
+            let c_i = c.coeff(i);
+            assert!(c_i == 0 || c_i == 1 || c_i == -1);
+            weight += (c_i != 0) as usize;
+        }
+        assert_eq!(weight, KAPPA);
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let ss = signature_scheme();
+        let a = uniform_poly();
+
+        let (mut s1, mut s2, mut t) = (ZZX::new(), ZZX::new(), ZZX::new());
+        ss.key_generation(&a, &mut s1, &mut s2, &mut t);
+
+        for msg in [&b"first message"[..], &b"second message"[..]] {
+            let sig = ss.sign(msg, &a, &s1, &s2);
+            assert!(ss.verify(msg, &sig, &a, &t));
+            assert!(!ss.verify(b"another message", &sig, &a, &t));
+
+            let mut forged = sig.clone();
+            forged.z1 += ZZX::new_with_val(1);
+            assert!(!ss.verify(msg, &forged, &a, &t));
+        }
+    }
+
+    #[test]
+    fn test_invalid_kappa() {
+        let sigma = Float::with_val(256, 250);
+        let m = Float::with_val(256, 3);
+        for kappa in [0, 65, P as usize + 1] {
+            assert!(matches!(
+                SignatureScheme::new(scheme(), kappa, sigma.clone(), m.clone()),
+                Err(Error::InvalidParameters(_))
+            ));
+        }
+    }
+}
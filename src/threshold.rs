@@ -0,0 +1,172 @@
+use rug::Integer;
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    error::{Error, Result},
+    util::{randombits_i64, zzx::ZZX},
+};
+
+/// Largest smudging noise (in bits), as `randombits_i64` overflows its mask at 63 bits
+pub const MAX_SMUDGING_BITS: u8 = 61;
+
+// n-out-of-n threshold decryption: the secret key r2 = sum_i r2_i is additively
+// shared between the parties and is never reconstructed.
+impl EncryptionScheme {
+    /// Distributed key generation, round 1: party i samples its share r2_i and
+    /// publishes p1_i = r1_i - a * r2_i
+    pub fn dkg_share(&self, a: &ZZX, r2_i: &mut ZZX, p1_i: &mut ZZX) {
+        self.key_generation(a, r2_i, p1_i);
+    }
+
+    /// Distributed key generation, round 2: p1 = sum_i p1_i is the joint public key.
+    /// Fails with `Error::InvalidShares` if there are no shares.
+    pub fn dkg_public_key(&self, p1: &mut ZZX, p1_shares: &[ZZX]) -> Result<()> {
+        if p1_shares.is_empty() {
+            return Err(Error::InvalidShares("no public key shares"));
+        }
+
+        let mut sum = ZZX::new();
+        for p1_i in p1_shares {
+            sum += p1_i.clone();
+        }
+        self._mod(&mut sum);
+
+        *p1 = sum;
+        Ok(())
+    }
+
+    /// d_i = c1 * r2_i + e_i, where the smudging noise e_i is uniform in
+    /// [-2^{smudging_bits}, 2^{smudging_bits}) and hides r2_i. Fails if
+    /// smudging_bits is above `MAX_SMUDGING_BITS`.
+    pub fn partial_decryption(
+        &self,
+        d_i: &mut ZZX,
+        c1: &ZZX,
+        r2_i: &ZZX,
+        smudging_bits: u8,
+    ) -> Result<()> {
+        let e_i = self.smudging_noise(smudging_bits)?;
+
+        *d_i = self.mulmod(c1, r2_i) + e_i;
+
+        self._mod(d_i);
+        Ok(())
+    }
+
+    /// A polynomial with coefficients uniform in [-2^{smudging_bits}, 2^{smudging_bits})
+    pub(crate) fn smudging_noise(&self, smudging_bits: u8) -> Result<ZZX> {
+        if smudging_bits > MAX_SMUDGING_BITS {
+            return Err(Error::InvalidBits {
+                bits: smudging_bits.into(),
+                min: 0,
+                max: MAX_SMUDGING_BITS.into(),
+            });
+        }
+
+        let mut e = ZZX::new();
+        e.set_length(self.p as usize);
+        for i in 0..self.p as usize {
+            let noise = randombits_i64(smudging_bits + 1) - (1 << smudging_bits);
+            e[i] = Integer::from(noise);
+        }
+        e.normalize();
+        Ok(e)
+    }
+
+    /// Sums the partial decryptions of all parties with c2 and decodes the result.
+    /// Fails with `Error::InvalidShares` if there are no partial decryptions.
+    pub fn combine_partial_decryptions(
+        &self,
+        m: &mut [i32],
+        c2: &ZZX,
+        partials: &[ZZX],
+    ) -> Result<()> {
+        if partials.is_empty() {
+            return Err(Error::InvalidShares("no partial decryptions"));
+        }
+
+        let mut sum = c2.clone();
+        for d_i in partials {
+            sum += d_i.clone();
+        }
+        self._mod(&mut sum);
+
+        self.decode(m, &sum);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::*;
+
+    const PARTIES: usize = 5;
+    const SMUDGING_BITS: u8 = 16;
+
+    #[test]
+    fn test_threshold_decryption() {
+        let es = scheme();
+        let a = uniform_poly();
+
+        // every party runs its share of the key generation locally
+        let mut r2_shares = vec![ZZX::new(); PARTIES];
+        let mut p1_shares = vec![ZZX::new(); PARTIES];
+        for (r2_i, p1_i) in r2_shares.iter_mut().zip(p1_shares.iter_mut()) {
+            es.dkg_share(&a, r2_i, p1_i);
+        }
+        let mut p1 = ZZX::new();
+        es.dkg_public_key(&mut p1, &p1_shares).unwrap();
+
+        check_round_trips(&es, &a, &p1, 10, |c1, c2| {
+            let partials: Vec<ZZX> = r2_shares
+                .iter()
+                .map(|r2_i| {
+                    let mut d_i = ZZX::new();
+                    es.partial_decryption(&mut d_i, c1, r2_i, SMUDGING_BITS)
+                        .unwrap();
+                    d_i
+                })
+                .collect();
+
+            let mut mdecoded = vec![0; P as usize];
+            es.combine_partial_decryptions(&mut mdecoded, c2, &partials)
+                .unwrap();
+            mdecoded
+        });
+    }
+
+    #[test]
+    fn test_no_shares() {
+        let es = scheme();
+        assert_eq!(
+            es.dkg_public_key(&mut ZZX::new(), &[]),
+            Err(Error::InvalidShares("no public key shares"))
+        );
+        assert_eq!(
+            es.combine_partial_decryptions(&mut [0; P as usize], &uniform_poly(), &[]),
+            Err(Error::InvalidShares("no partial decryptions"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_smudging_bits() {
+        let es = scheme();
+        let (c1, r2_i) = (uniform_poly(), uniform_poly());
+        let mut d_i = ZZX::new();
+
+        for bits in [MAX_SMUDGING_BITS + 1, u8::MAX] {
+            assert_eq!(
+                es.partial_decryption(&mut d_i, &c1, &r2_i, bits),
+                Err(Error::InvalidBits {
+                    bits: bits.into(),
+                    min: 0,
+                    max: MAX_SMUDGING_BITS.into()
+                })
+            );
+        }
+        assert!(es
+            .partial_decryption(&mut d_i, &c1, &r2_i, MAX_SMUDGING_BITS)
+            .is_ok());
+    }
+}
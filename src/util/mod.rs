@@ -1,14 +1,41 @@
 pub mod zzx;
 
-use rand::Rng;
+#[cfg(test)]
+pub(crate) mod test_util;
+
+use crate::{
+    drbg::randombytes,
+    error::{Error, Result},
+};
+
+/// A uniform u64 from `randombytes` (little endian)
+pub fn random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    randombytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
 
 /// Equivalent to `NTL::RandomBits_ulong`
 pub fn randombits_u64(bits: u8) -> u64 {
-    assert!(bits > 0 && bits <= 64, "Bits must be between 1 and 64");
+    try_randombits_u64(bits).expect("Bits must be between 1 and 64")
+}
+
+/// Equivalent to `NTL::RandomBits_long`
+pub fn randombits_i64(bits: u8) -> i64 {
+    try_randombits_i64(bits).expect("Bits must be between 1 and 64")
+}
+
+/// `randombits_u64`, or an error if bits is not between 1 and 64
+pub fn try_randombits_u64(bits: u8) -> Result<u64> {
+    if bits == 0 || bits > 64 {
+        return Err(Error::InvalidBits {
+            bits: bits.into(),
+            min: 1,
+            max: 64,
+        });
+    }
 
-    // Generate a random u64
-    let mut rng = rand::rng();
-    let random_value: u64 = rng.random::<u64>();
+    let random_value = random_u64();
 
     // Mask the result to ensure it fits within the specified number of bits
     let mask = if bits == 64 {
@@ -16,16 +43,20 @@ pub fn randombits_u64(bits: u8) -> u64 {
     } else {
         (1 << bits) - 1
     };
-    random_value & mask
+    Ok(random_value & mask)
 }
 
-/// Equivalent to `NTL::RandomBits_long`
-pub fn randombits_i64(bits: u8) -> i64 {
-    assert!(bits > 0 && bits <= 64, "Bits must be between 1 and 64");
+/// `randombits_i64`, or an error if bits is not between 1 and 64
+pub fn try_randombits_i64(bits: u8) -> Result<i64> {
+    if bits == 0 || bits > 64 {
+        return Err(Error::InvalidBits {
+            bits: bits.into(),
+            min: 1,
+            max: 64,
+        });
+    }
 
-    // Generate a random u64
-    let mut rng = rand::rng();
-    let random_value: i64 = rng.random::<i64>();
+    let random_value = random_u64() as i64;
 
     // Mask the result to ensure it fits within the specified number of bits
     let mask = if bits == 64 {
@@ -33,5 +64,78 @@ pub fn randombits_i64(bits: u8) -> i64 {
     } else {
         (1 << bits) - 1
     };
-    random_value & mask
+    Ok(random_value & mask)
+}
+
+/// a == b, in a time that depends on their lengths only
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Words drawn from the generator at a time by `RandomBits`
+const RANDOM_BLOCK_WORDS: usize = 32;
+
+/// Random bits handed out a few at a time from a buffer of random words, so that
+/// callers needing a few bits per operation do not draw a word each time
+#[derive(Debug, Clone)]
+pub struct RandomBits {
+    block: [u64; RANDOM_BLOCK_WORDS],
+    next: usize,
+    /* the `left` next bits, from the least significant one */
+    bits: u128,
+    left: u32,
+}
+
+impl RandomBits {
+    pub fn new() -> Self {
+        Self {
+            block: [0; RANDOM_BLOCK_WORDS],
+            next: RANDOM_BLOCK_WORDS,
+            bits: 0,
+            left: 0,
+        }
+    }
+
+    pub fn bit(&mut self) -> u32 {
+        self.take(1)
+    }
+
+    /// The next n <= 32 bits as an integer, the first one being the least significant
+    pub fn take(&mut self, n: u32) -> u32 {
+        let value = self.peek(n);
+        self.skip(n);
+        value
+    }
+
+    /// The next n <= 32 bits, without consuming them
+    pub fn peek(&mut self, n: u32) -> u32 {
+        debug_assert!(n <= 32);
+        if self.left < n {
+            if self.next == RANDOM_BLOCK_WORDS {
+                let mut bytes = [0u8; 8 * RANDOM_BLOCK_WORDS];
+                randombytes(&mut bytes);
+                for (word, chunk) in self.block.iter_mut().zip(bytes.chunks(8)) {
+                    *word = u64::from_le_bytes(chunk.try_into().unwrap());
+                }
+                self.next = 0;
+            }
+            self.bits |= (self.block[self.next] as u128) << self.left;
+            self.next += 1;
+            self.left += 64;
+        }
+        (self.bits & ((1 << n) - 1)) as u32
+    }
+
+    /// Consumes n bits, which must have been peeked
+    pub fn skip(&mut self, n: u32) {
+        debug_assert!(n <= self.left);
+        self.bits >>= n;
+        self.left -= n;
+    }
+}
+
+impl Default for RandomBits {
+    fn default() -> Self {
+        Self::new()
+    }
 }
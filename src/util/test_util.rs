@@ -0,0 +1,49 @@
+use rug::Float;
+
+use crate::{
+    encryption_scheme::EncryptionScheme,
+    sampling::sample_uniform_mod_q,
+    util::{randombits_i64, zzx::ZZX, RandomBits},
+};
+
+/// Parameters of the ALTERNATE set in `main.rs`
+pub const P: i32 = 14;
+pub const Q: i32 = 179424673;
+
+pub fn scheme() -> EncryptionScheme {
+    let precision = 256;
+    let sigma = Float::with_val(precision, 2.0);
+    let center = Float::with_val(precision, 0);
+    EncryptionScheme::new(P, Q, precision, 13.2, sigma, center)
+}
+
+pub fn uniform_poly() -> ZZX {
+    let mut a = vec![0; P as usize];
+    sample_uniform_mod_q(&mut a, Q, &mut RandomBits::new());
+    ZZX::new_with_vec(a)
+}
+
+pub fn random_message() -> Vec<i32> {
+    (0..P).map(|_| randombits_i64(1) as i32).collect()
+}
+
+/// Encrypts `trials` random messages under the public key (a, p1), and checks that
+/// `decrypt` recovers each of them from its ciphertext (c1, c2)
+pub fn check_round_trips(
+    es: &EncryptionScheme,
+    a: &ZZX,
+    p1: &ZZX,
+    trials: usize,
+    mut decrypt: impl FnMut(&ZZX, &ZZX) -> Vec<i32>,
+) {
+    for _ in 0..trials {
+        let m = random_message();
+        let mut mprime = ZZX::new();
+        es.encode(&mut mprime, &m);
+
+        let (mut c1, mut c2) = (ZZX::new(), ZZX::new());
+        es.encryption(&mut c1, &mut c2, a, p1, &mprime);
+
+        assert_eq!(decrypt(&c1, &c2), m);
+    }
+}
@@ -9,6 +9,8 @@ use rug::{
     Complete, Integer,
 };
 
+use crate::error::{Error, Result};
+
 /// Custom clone of NTL::ZZX
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ZZX {
@@ -42,11 +44,9 @@ impl ZZX {
 
         let coeffs: Vec<Integer> = coeffs.into_iter().map(Into::into).collect();
 
-        if coeffs.iter().all(|x| x.is_zero()) {
-            return ZZX::new();
-        }
-
-        ZZX { coeffs }
+        let mut x = ZZX { coeffs };
+        x.normalize();
+        x
     }
 
     /// intial value 0, but space is pre-allocated for n coefficients
@@ -125,15 +125,17 @@ impl ZZX {
     where
         T: Into<Integer>,
     {
+        let n = n.map(Into::into).unwrap_or(Integer::from(1));
+
         let m = self.deg();
-        if i as i64 > m && self.is_zero() {
+        if i as i64 > m && n == 0 {
             return;
         }
 
         if i as i64 > m {
             self.set_length(i + 1);
         }
-        self.coeffs[i] = n.map(Into::into).unwrap_or(Integer::from(1));
+        self.coeffs[i] = n;
 
         self.normalize();
     }
@@ -212,14 +214,28 @@ impl IndexMut<usize> for ZZX {
 
 // Modular arithmetic -- f must be monic, and other args
 // must have degree less than that of f
-fn _mulmod(x: &mut ZZX, a: &ZZX, b: &ZZX, f: &ZZX) {
-    if a.deg() >= f.deg() || b.deg() >= f.deg() || f.deg() == 0 || f.lead_coeff() != 1 {
-        panic!("MulMod: bad args");
+fn _try_mulmod(x: &mut ZZX, a: &ZZX, b: &ZZX, f: &ZZX) -> Result<()> {
+    if f.deg() <= 0 || f.lead_coeff() != 1 {
+        return Err(Error::InvalidModulus);
+    }
+    for c in [a, b] {
+        if c.deg() >= f.deg() {
+            return Err(Error::WrongDegree {
+                bound: f.deg(),
+                found: c.deg(),
+            });
+        }
     }
 
     let mut t = ZZX::new();
     mul(&mut t, a, b);
-    rem(x, &t, f);
+    _try_rem(x, &t, f)
+}
+
+fn _mulmod(x: &mut ZZX, a: &ZZX, b: &ZZX, f: &ZZX) {
+    if _try_mulmod(x, a, b, f).is_err() {
+        panic!("MulMod: bad args");
+    }
 }
 
 pub fn mulmod(a: &ZZX, b: &ZZX, f: &ZZX) -> ZZX {
@@ -228,6 +244,13 @@ pub fn mulmod(a: &ZZX, b: &ZZX, f: &ZZX) -> ZZX {
     x
 }
 
+/// a * b % f, or an error if f is not monic or a, b are not reduced mod f
+pub fn try_mulmod(a: &ZZX, b: &ZZX, f: &ZZX) -> Result<ZZX> {
+    let mut x = ZZX::new();
+    _try_mulmod(&mut x, a, b, f)?;
+    Ok(x)
+}
+
 fn _sqrmod(x: &mut ZZX, a: &ZZX, f: &ZZX) {
     if a.deg() >= f.deg() || f.deg() == 0 || f.lead_coeff() != 1 {
         panic!("SqrMod: bad args");
@@ -344,11 +367,24 @@ impl MulAssign for ZZX {
 }
 
 pub fn rem(r: &mut ZZX, a: &ZZX, b: &ZZX) {
+    if let Err(e) = _try_rem(r, a, b) {
+        panic!("rem: {}", e);
+    }
+}
+
+/// a % b, or an error if b is zero or the remainder is not defined over ZZ
+pub fn try_rem(a: &ZZX, b: &ZZX) -> Result<ZZX> {
+    let mut r = ZZX::new();
+    _try_rem(&mut r, a, b)?;
+    Ok(r)
+}
+
+fn _try_rem(r: &mut ZZX, a: &ZZX, b: &ZZX) -> Result<()> {
     let da = a.deg();
     let db = b.deg();
 
     if db < 0 {
-        panic!("rem: division by zero");
+        return Err(Error::DivisionByZero);
     }
 
     if da < db {
@@ -368,9 +404,10 @@ pub fn rem(r: &mut ZZX, a: &ZZX, b: &ZZX) {
         pseudo_rem(&mut r1, a, b);
         let m = b.lead_coeff().pow((da - db + 1) as u32);
         if !divide_with_integer(r, &r1, &m) {
-            panic!("rem: remainder not defined over ZZ");
+            return Err(Error::InexactDivision);
         }
     }
+    Ok(())
 }
 
 fn const_rem(r: &mut ZZX, _a: &ZZX, b: &Integer) {
@@ -465,18 +502,35 @@ fn pseudo_div_rem(q: &mut ZZX, r: &mut ZZX, a: &ZZX, b: &ZZX) {
 }
 
 fn div_rem(q: &mut ZZX, r: &mut ZZX, a: &ZZX, b: &ZZX) {
+    if let Err(e) = _try_div_rem(q, r, a, b) {
+        panic!("div_rem: {}", e);
+    }
+}
+
+/// (a / b, a % b), or an error if b is zero or the division is not defined over ZZ
+pub fn try_div_rem(a: &ZZX, b: &ZZX) -> Result<(ZZX, ZZX)> {
+    let mut q = ZZX::new();
+    let mut r = ZZX::new();
+    _try_div_rem(&mut q, &mut r, a, b)?;
+    Ok((q, r))
+}
+
+fn _try_div_rem(q: &mut ZZX, r: &mut ZZX, a: &ZZX, b: &ZZX) -> Result<()> {
     let da = a.deg();
     let db = b.deg();
 
     if db < 0 {
-        panic!("div_rem: division by zero");
+        return Err(Error::DivisionByZero);
     }
 
     if da < db {
         r.coeffs = a.coeffs.clone();
         q.clear();
     } else if db == 0 {
-        const_div_rem(q, r, a, &b.const_term());
+        if !divide_with_integer(q, a, &b.const_term()) {
+            return Err(Error::InexactDivision);
+        }
+        r.clear();
     } else if b.lead_coeff() == 1 {
         pseudo_div_rem(q, r, a, b);
     } else if b.lead_coeff() == -1 {
@@ -491,37 +545,40 @@ fn div_rem(q: &mut ZZX, r: &mut ZZX, a: &ZZX, b: &ZZX) {
         let mut r1 = ZZX::new();
         pseudo_div_rem(&mut q1, &mut r1, a, b);
         let m = b.lead_coeff().pow((da - db + 1) as u32);
-        if !divide_with_integer(q, &q1, &m) {
-            panic!("div_rem: quotient not defined over ZZ");
-        }
-        if !divide_with_integer(r, &r1, &m) {
-            panic!("div_rem: remainder not defined over ZZ");
+        if !divide_with_integer(q, &q1, &m) || !divide_with_integer(r, &r1, &m) {
+            return Err(Error::InexactDivision);
         }
     }
+    Ok(())
 }
 
-fn const_div_rem(q: &mut ZZX, r: &mut ZZX, a: &ZZX, b: &Integer) {
-    if b == &0 {
-        panic!("const_div_rem: division by zero");
-    }
-    if !divide_with_integer(q, a, b) {
-        panic!("const_div_rem: quotient not defined over ZZ");
+fn div(q: &mut ZZX, a: &ZZX, b: &ZZX) {
+    if let Err(e) = _try_div(q, a, b) {
+        panic!("div: {}", e);
     }
-    r.clear();
 }
 
-fn div(q: &mut ZZX, a: &ZZX, b: &ZZX) {
+/// a / b, or an error if b is zero or the quotient is not defined over ZZ
+pub fn try_div(a: &ZZX, b: &ZZX) -> Result<ZZX> {
+    let mut q = ZZX::new();
+    _try_div(&mut q, a, b)?;
+    Ok(q)
+}
+
+fn _try_div(q: &mut ZZX, a: &ZZX, b: &ZZX) -> Result<()> {
     let da = a.deg();
     let db = b.deg();
 
     if db < 0 {
-        panic!("div: division by zero");
+        return Err(Error::DivisionByZero);
     }
 
     if da < db {
         q.clear();
     } else if db == 0 {
-        div_with_integer(q, a, &b.const_term());
+        if !divide_with_integer(q, a, &b.const_term()) {
+            return Err(Error::InexactDivision);
+        }
     } else if b.lead_coeff() == 1 {
         pseudo_div(q, a, b);
     } else if b.lead_coeff() == -1 {
@@ -536,9 +593,10 @@ fn div(q: &mut ZZX, a: &ZZX, b: &ZZX) {
         pseudo_div(&mut q1, a, b);
         let m = b.lead_coeff().pow((da - db + 1) as u32);
         if !divide_with_integer(q, &q1, &m) {
-            panic!("div: quotient not defined over ZZ");
+            return Err(Error::InexactDivision);
         }
     }
+    Ok(())
 }
 
 fn div_with_integer(q: &mut ZZX, a: &ZZX, b: &Integer) {
@@ -1596,6 +1654,163 @@ fn _inner_product(res: &mut Integer, a: &[Integer], b: &[Integer]) {
     *res = acc;
 }
 
+/*************************
+ * Arithmetic over Z_p[x]
+ *************************/
+/// x = a with coefficients reduced to [0, p)
+fn _reduce_coeffs(x: &mut ZZX, a: &ZZX, p: &Integer) {
+    x.coeffs = a
+        .coeffs
+        .iter()
+        .map(|c| c.div_rem_euc_ref(p).complete().1)
+        .collect();
+    x.normalize();
+}
+
+/// q = a / b, r = a % b over Z_p[x]. a and b must be reduced.
+/// Returns false if b is zero or its leading coefficient is not invertible mod p.
+fn div_rem_mod(q: &mut ZZX, r: &mut ZZX, a: &ZZX, b: &ZZX, p: &Integer) -> bool {
+    let db = b.deg();
+    if db < 0 {
+        return false;
+    }
+
+    let lead_inv = match b.lead_coeff().invert(p) {
+        Ok(lead_inv) => lead_inv,
+        Err(_) => return false,
+    };
+
+    let mut rem = a.clone();
+    let da = a.deg();
+    if da < db {
+        q.clear();
+        *r = rem;
+        return true;
+    }
+
+    let mut quot = ZZX::new();
+    quot.set_length((da - db + 1) as usize);
+    for k in (0..=(da - db) as usize).rev() {
+        let t = (rem.coeffs[k + db as usize].clone() * &lead_inv) % p;
+        if t == 0 {
+            continue;
+        }
+        for j in 0..=db as usize {
+            let c = rem.coeffs[k + j].clone() - t.clone() * &b.coeffs[j];
+            rem.coeffs[k + j] = c.div_rem_euc_ref(p).complete().1;
+        }
+        quot.coeffs[k] = t;
+    }
+    quot.normalize();
+    rem.normalize();
+
+    *q = quot;
+    *r = rem;
+    true
+}
+
+/// d = gcd(a, b) over Z_p[x], d monic (or zero), and s * a + t * b = d.
+/// p should be prime; returns false if p < 2 or a non-invertible leading coefficient
+/// shows up.
+fn _xgcd(d: &mut ZZX, s: &mut ZZX, t: &mut ZZX, a: &ZZX, b: &ZZX, p: &Integer) -> bool {
+    if *p < 2 {
+        return false;
+    }
+
+    let mut r0 = ZZX::new();
+    let mut r1 = ZZX::new();
+    _reduce_coeffs(&mut r0, a, p);
+    _reduce_coeffs(&mut r1, b, p);
+
+    // invariant: s_i * a + t_i * b = r_i
+    let mut s0 = ZZX::new_with_val(1);
+    let mut s1 = ZZX::new();
+    let mut t0 = ZZX::new();
+    let mut t1 = ZZX::new_with_val(1);
+
+    let mut quot = ZZX::new();
+    let mut rem = ZZX::new();
+    while !r1.is_zero() {
+        if !div_rem_mod(&mut quot, &mut rem, &r0, &r1, p) {
+            return false;
+        }
+
+        let mut s2 = ZZX::new();
+        _reduce_coeffs(&mut s2, &(&s0 - &(&quot * &s1)), p);
+        let mut t2 = ZZX::new();
+        _reduce_coeffs(&mut t2, &(&t0 - &(&quot * &t1)), p);
+
+        r0 = std::mem::replace(&mut r1, rem.clone());
+        s0 = std::mem::replace(&mut s1, s2);
+        t0 = std::mem::replace(&mut t1, t2);
+    }
+
+    if r0.is_zero() {
+        d.clear();
+        *s = s0;
+        *t = t0;
+        return true;
+    }
+
+    // make the gcd monic
+    let lead_inv = match r0.lead_coeff().invert(p) {
+        Ok(lead_inv) => ZZX::new_with_val(lead_inv),
+        Err(_) => return false,
+    };
+    _reduce_coeffs(d, &(&r0 * &lead_inv), p);
+    _reduce_coeffs(s, &(&s0 * &lead_inv), p);
+    _reduce_coeffs(t, &(&t0 * &lead_inv), p);
+    true
+}
+
+/// (d, s, t) with d = gcd(a, b) over Z_p[x] monic, and s * a + t * b = d, or None
+/// if p < 2, or p is not prime and Euclid hits a non-invertible leading coefficient
+pub fn xgcd(a: &ZZX, b: &ZZX, p: &Integer) -> Option<(ZZX, ZZX, ZZX)> {
+    let mut d = ZZX::new();
+    let mut s = ZZX::new();
+    let mut t = ZZX::new();
+    if _xgcd(&mut d, &mut s, &mut t, a, b, p) {
+        Some((d, s, t))
+    } else {
+        None
+    }
+}
+
+/// x = a^{-1} % f over Z_q[x]. Returns false if a is not invertible, f is constant
+/// (or zero), or q is not prime and Euclid hits a zero divisor.
+fn _invmod(x: &mut ZZX, a: &ZZX, f: &ZZX, q: &Integer) -> bool {
+    if f.deg() <= 0 {
+        return false;
+    }
+
+    let mut d = ZZX::new();
+    let mut s = ZZX::new();
+    let mut t = ZZX::new();
+    if !_xgcd(&mut d, &mut s, &mut t, a, f, q) || !d.is_one() {
+        return false;
+    }
+
+    let mut f_reduced = ZZX::new();
+    _reduce_coeffs(&mut f_reduced, f, q);
+    let mut r = ZZX::new();
+    let mut quot = ZZX::new();
+    if !div_rem_mod(&mut quot, &mut r, &s, &f_reduced, q) {
+        return false;
+    }
+    *x = r;
+    true
+}
+
+/// a^{-1} in Z_q[x]/(f), or None if a is not invertible or f is constant. q must be prime.
+pub fn invmod(a: &ZZX, f: &ZZX, q: &Integer) -> Option<ZZX> {
+    let mut x = ZZX::new();
+    if _invmod(&mut x, a, f, q) {
+        Some(x)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1723,6 +1938,105 @@ mod tests {
         assert_eq!(c[1], 1);
     }
 
+    #[test]
+    fn test_set_coeff() {
+        let mut a = ZZX::new();
+        a.set_coeff(0, Some(0));
+        a.set_coeff(1, Some(2));
+        assert_eq!(a.deg(), 1);
+        assert_eq!(a[0], 0);
+        assert_eq!(a[1], 2);
+
+        a.set_coeff(3, Some(0));
+        assert_eq!(a.deg(), 1);
+
+        let a = ZZX::new_with_vec(vec![0, 1]);
+        let b = ZZX::new_with_val(3);
+        let c = &a * &b;
+        assert_eq!(c[0], 0);
+        assert_eq!(c[1], 3);
+    }
+
+    #[test]
+    fn test_xgcd() {
+        let p = Integer::from(7);
+
+        // (x + 1)(x + 2) and (x + 1)(x + 3)
+        let a = ZZX::new_with_vec(vec![2, 3, 1]);
+        let b = ZZX::new_with_vec(vec![3, 4, 1]);
+        let (d, s, t) = xgcd(&a, &b, &p).unwrap();
+        assert_eq!(d, ZZX::new_with_vec(vec![1, 1]));
+
+        let mut check = ZZX::new();
+        _reduce_coeffs(&mut check, &(&(&s * &a) + &(&t * &b)), &p);
+        assert_eq!(check, d);
+
+        let (d, _, _) = xgcd(&a, &ZZX::new(), &p).unwrap();
+        assert_eq!(d, a);
+
+        // 2 is not invertible mod 8
+        let b = ZZX::new_with_vec(vec![1, 2]);
+        assert!(xgcd(&a, &b, &Integer::from(8)).is_none());
+        for p in [0, 1] {
+            assert!(xgcd(&a, &b, &Integer::from(p)).is_none());
+        }
+    }
+
+    #[test]
+    fn test_invmod() {
+        let q = Integer::from(4591);
+        // x^7 - x - 1
+        let f = ZZX::new_with_vec(vec![-1, -1, 0, 0, 0, 0, 0, 1]);
+
+        let a = ZZX::new_with_vec(vec![3, -1, 0, 1, 1, 0, -1]);
+        let inv = invmod(&a, &f, &q).unwrap();
+        assert!(inv.deg() < f.deg());
+
+        let mut check = ZZX::new();
+        _reduce_coeffs(&mut check, &(&(&a * &inv) % &f), &q);
+        assert!(check.is_one());
+
+        // x + 1 divides x^2 - 1
+        let f = ZZX::new_with_vec(vec![-1, 0, 1]);
+        let a = ZZX::new_with_vec(vec![1, 1]);
+        assert!(invmod(&a, &f, &q).is_none());
+        assert!(invmod(&ZZX::new(), &f, &q).is_none());
+
+        // constant and zero moduli
+        assert!(invmod(&a, &ZZX::new_with_val(5), &q).is_none());
+        assert!(invmod(&a, &ZZX::new(), &q).is_none());
+
+        let mut quot = ZZX::new();
+        let mut rem = ZZX::new();
+        assert!(!div_rem_mod(&mut quot, &mut rem, &a, &ZZX::new(), &q));
+    }
+
+    #[test]
+    fn test_try_mulmod() {
+        // x^3 + 1
+        let f = ZZX::new_with_vec(vec![1, 0, 0, 1]);
+        let a = ZZX::new_with_vec(vec![1, 2, 3]);
+        let b = ZZX::new_with_vec(vec![0, 1]);
+        assert_eq!(
+            try_mulmod(&a, &b, &f),
+            Ok(ZZX::new_with_vec(vec![-3, 1, 2]))
+        );
+
+        let big = ZZX::new_with_vec(vec![1, 0, 0, 0, 1]);
+        assert_eq!(
+            try_mulmod(&big, &b, &f),
+            Err(Error::WrongDegree { bound: 3, found: 4 })
+        );
+        let not_monic = ZZX::new_with_vec(vec![1, 0, 0, 2]);
+        assert_eq!(try_mulmod(&a, &b, &not_monic), Err(Error::InvalidModulus));
+
+        assert_eq!(try_rem(&a, &ZZX::new()), Err(Error::DivisionByZero));
+        assert_eq!(
+            try_div(&a, &ZZX::new_with_val(2)),
+            Err(Error::InexactDivision)
+        );
+    }
+
     #[test]
     fn test_left_shift() {
         let a = ZZX::new_with_vec(vec![1, 2, 3]);
@@ -0,0 +1,234 @@
+use std::borrow::Cow;
+
+use crate::{
+    encryption_scheme::{EncryptionScheme, RlweCiphertext, RlwePublicKey, RlweSecretKey},
+    error::{Error, Result},
+    util::zzx::ZZX,
+};
+
+// Validation of the keys, messages and ciphertexts given to the fallible APIs
+// (`try_*`, `Encrypt`, `Decrypt`) of an `EncryptionScheme`.
+//
+// Lengths and structural problems are always rejected. Values out of range are
+// rejected in strict mode and reduced in lenient mode: coefficients mod q,
+// polynomials mod f and message bits mod 2.
+
+/// How out of range values are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Validation {
+    /// Reject them with an error
+    #[default]
+    Strict,
+    /// Reduce them into range
+    Lenient,
+}
+
+impl EncryptionScheme {
+    pub(crate) fn check_degree(&self, a: &ZZX) -> Result<()> {
+        if a.deg() >= self.p as i64 {
+            return Err(Error::WrongDegree {
+                bound: self.p as i64,
+                found: a.deg(),
+            });
+        }
+        Ok(())
+    }
+
+    /// a has degree < p and coefficients in [0, q)
+    pub(crate) fn check_reduced(&self, a: &ZZX) -> Result<()> {
+        self.check_degree(a)?;
+        for i in 0..self.p as usize {
+            let a_i = a.coeff(i);
+            if a_i < 0 || a_i >= self.q {
+                return Err(Error::ModulusMismatch {
+                    q: self.q.into(),
+                    found: a_i,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_message_len(&self, len: usize) -> Result<()> {
+        if len != self.p as usize {
+            return Err(Error::MessageLength {
+                expected: self.p as usize,
+                found: len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Small polynomial of a secret: degree < p and coefficients within the bound of the
+    /// secret distribution
+    pub(crate) fn validate_short_poly<'a>(&self, a: &'a ZZX) -> Result<Cow<'a, ZZX>> {
+        match self.validation() {
+            Validation::Strict => {
+                self.check_degree(a)?;
+                let bound = self.secret_bound();
+                if (0..self.p as usize).any(|i| a.coeff(i).abs() > bound) {
+                    return Err(Error::MalformedKey(
+                        "secret coefficients exceed the bound of their distribution",
+                    ));
+                }
+                Ok(Cow::Borrowed(a))
+            }
+            Validation::Lenient => Ok(Cow::Owned(self.modulus().reduce(a, self.p as usize))),
+        }
+    }
+
+    /// An element of R_q: degree < p and coefficients in [0, q)
+    pub fn validate_poly<'a>(&self, a: &'a ZZX) -> Result<Cow<'a, ZZX>> {
+        match self.validation() {
+            Validation::Strict => {
+                self.check_reduced(a)?;
+                Ok(Cow::Borrowed(a))
+            }
+            Validation::Lenient => {
+                let mut r = self.modulus().reduce(a, self.p as usize);
+                self._mod(&mut r);
+                r.normalize();
+                Ok(Cow::Owned(r))
+            }
+        }
+    }
+
+    /// A message of exactly p bits
+    pub fn validate_message<'a>(&self, m: &'a [i32]) -> Result<Cow<'a, [i32]>> {
+        self.check_message_len(m.len())?;
+        match self.validation() {
+            Validation::Strict => {
+                if let Some((index, &value)) = m.iter().enumerate().find(|(_, &b)| b != 0 && b != 1)
+                {
+                    return Err(Error::NonBinaryMessage { index, value });
+                }
+                Ok(Cow::Borrowed(m))
+            }
+            Validation::Lenient => Ok(Cow::Owned(m.iter().map(|b| b.rem_euclid(2)).collect())),
+        }
+    }
+
+    /// a and p1 elements of R_q, with a non-zero
+    pub fn validate_public_key<'a>(&self, pk: &'a RlwePublicKey) -> Result<Cow<'a, RlwePublicKey>> {
+        let a = self.validate_poly(&pk.a)?;
+        let p1 = self.validate_poly(&pk.p1)?;
+        // c1 = e2 would not hide anything
+        if a.is_zero() {
+            return Err(Error::MalformedKey("a is zero"));
+        }
+
+        match (a, p1) {
+            (Cow::Borrowed(_), Cow::Borrowed(_)) => Ok(Cow::Borrowed(pk)),
+            (a, p1) => Ok(Cow::Owned(RlwePublicKey {
+                a: a.into_owned(),
+                p1: p1.into_owned(),
+            })),
+        }
+    }
+
+    /// r2 of degree < p, with coefficients within the bound of the secret distribution
+    /// in strict mode
+    pub fn validate_secret_key<'a>(&self, sk: &'a RlweSecretKey) -> Result<Cow<'a, RlweSecretKey>> {
+        match self.validate_short_poly(&sk.r2)? {
+            Cow::Borrowed(_) => Ok(Cow::Borrowed(sk)),
+            Cow::Owned(r2) => Ok(Cow::Owned(RlweSecretKey { r2 })),
+        }
+    }
+
+    /// c1 and c2 elements of R_q
+    pub fn validate_ciphertext<'a>(
+        &self,
+        ct: &'a RlweCiphertext,
+    ) -> Result<Cow<'a, RlweCiphertext>> {
+        let c1 = self.validate_poly(&ct.c1)?;
+        let c2 = self.validate_poly(&ct.c2)?;
+
+        match (c1, c2) {
+            (Cow::Borrowed(_), Cow::Borrowed(_)) => Ok(Cow::Borrowed(ct)),
+            (c1, c2) => Ok(Cow::Owned(RlweCiphertext {
+                c1: c1.into_owned(),
+                c2: c2.into_owned(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pke::{Decrypt, Encrypt, KeyGen},
+        sampling::SecretDistribution,
+        util::test_util::*,
+    };
+
+    #[test]
+    fn test_strict_validation() {
+        let es = scheme();
+        let (pk, sk) = es.generate_keys();
+        assert!(matches!(es.validate_public_key(&pk), Ok(Cow::Borrowed(_))));
+        assert!(matches!(es.validate_secret_key(&sk), Ok(Cow::Borrowed(_))));
+
+        let mut m = random_message();
+        m[3] = 2;
+        assert_eq!(
+            es.encrypt(&pk, &m),
+            Err(Error::NonBinaryMessage { index: 3, value: 2 })
+        );
+
+        let zero_a = RlwePublicKey {
+            a: ZZX::new(),
+            p1: pk.p1.clone(),
+        };
+        assert_eq!(
+            es.validate_public_key(&zero_a),
+            Err(Error::MalformedKey("a is zero"))
+        );
+
+        let large = RlweSecretKey {
+            r2: ZZX::new_with_val(Q / 2),
+        };
+        assert!(matches!(
+            es.decrypt(&large, &es.encrypt(&pk, &random_message()).unwrap()),
+            Err(Error::MalformedKey(_))
+        ));
+
+        // within the gaussian bound, but not a binary secret
+        let two = RlweSecretKey {
+            r2: ZZX::new_with_val(2),
+        };
+        assert!(es.validate_secret_key(&two).is_ok());
+        let binary = scheme().with_secret_distribution(SecretDistribution::Binary);
+        assert!(matches!(
+            binary.validate_secret_key(&two),
+            Err(Error::MalformedKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_validation() {
+        let es = scheme().with_validation(Validation::Lenient);
+        let (pk, sk) = es.generate_keys();
+
+        // bits are taken mod 2
+        let m = random_message();
+        let shifted: Vec<i32> = m.iter().map(|b| b + 2).collect();
+        let mut ct = es.encrypt(&pk, &shifted).unwrap();
+
+        // coefficients are taken mod q and polynomials mod f: x^p + 1 = 0
+        ct.c2[0] += Q;
+        ct.c2[1] -= Q;
+        let mut x_p = ZZX::new();
+        x_p[P as usize] = 1.into();
+        ct.c1 += x_p;
+        ct.c1[0] += 1;
+
+        assert_eq!(es.decrypt(&sk, &ct), Ok(m));
+
+        // lengths are never fixed up
+        assert!(matches!(
+            es.encrypt(&pk, &shifted[1..]),
+            Err(Error::MessageLength { .. })
+        ));
+    }
+}
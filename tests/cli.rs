@@ -0,0 +1,89 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+// Runs the rlwe binary: key generation, sealing stdin into an envelope and opening
+// it again, as in the README.
+
+fn rlwe(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlwe"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn check_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Path of a key in the scratch directory of the integration tests
+fn key_path(name: &str) -> String {
+    format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name)
+}
+
+fn keygen(key: &str) -> Output {
+    rlwe(
+        &[
+            "keygen",
+            "--params",
+            "toy",
+            "--out",
+            key,
+            "--insecure-test-params",
+        ],
+        b"",
+    )
+}
+
+#[test]
+fn test_keygen_encrypt_decrypt() {
+    let key = key_path("round_trip");
+    let (pk, sk) = (format!("{}.pub", key), format!("{}.sec", key));
+    check_success(&keygen(&key));
+
+    let plaintext = b"attack at dawn";
+    let envelope = rlwe(
+        &["encrypt", "--pk", &pk, "--insecure-test-params"],
+        plaintext,
+    );
+    check_success(&envelope);
+
+    let opened = rlwe(&["decrypt", "--sk", &sk], &envelope.stdout);
+    check_success(&opened);
+    assert_eq!(opened.stdout, plaintext);
+
+    // toy messages are too short for real data
+    let refused = rlwe(&["encrypt", "--pk", &pk], plaintext);
+    assert_eq!(refused.status.code(), Some(64));
+    assert!(refused.stdout.is_empty());
+
+    let mut tampered = envelope.stdout;
+    *tampered.last_mut().unwrap() ^= 1;
+    let rejected = rlwe(&["decrypt", "--sk", &sk], &tampered);
+    assert_eq!(rejected.status.code(), Some(65));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_secret_key_permissions() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let key = key_path("permissions");
+    let sk = format!("{}.sec", key);
+    fs::write(&sk, b"stale").unwrap();
+    fs::set_permissions(&sk, fs::Permissions::from_mode(0o644)).unwrap();
+
+    check_success(&keygen(&key));
+    let mode = fs::metadata(&sk).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
@@ -0,0 +1,45 @@
+use rlwe_rs::{
+    kat::{kat_record, kat_seeds, parse_kats, KAT_COUNT},
+    params::{ParameterSet, NTRUP761, RLWE1024, TOY},
+};
+
+// Regenerates every record of the files written by `rlwe kat --out kat` and
+// compares them byte for byte. A failure means keys, ciphertexts or decryptions
+// changed for a fixed seed: either a regression in sampling, arithmetic or
+// encoding, or an intended change, after which the files must be regenerated.
+
+fn check_kats(params: &ParameterSet) {
+    let path = format!("{}/kat/{}.rsp", env!("CARGO_MANIFEST_DIR"), params.name);
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let (name, records) = parse_kats(&text).unwrap();
+    assert_eq!(name, params.name);
+    assert_eq!(records.len(), KAT_COUNT);
+
+    for ((i, expected), seed) in records.iter().enumerate().zip(kat_seeds(KAT_COUNT)) {
+        assert_eq!(expected.count, i);
+        assert_eq!(expected.seed, seed);
+        let record = kat_record(params, expected.count, &expected.seed).unwrap();
+        assert!(
+            record == *expected,
+            "record {} of {} does not match",
+            i,
+            path
+        );
+        assert_eq!(record.decrypted, record.msg);
+    }
+}
+
+#[test]
+fn test_rlwe1024_kats() {
+    check_kats(&RLWE1024);
+}
+
+#[test]
+fn test_toy_kats() {
+    check_kats(&TOY);
+}
+
+#[test]
+fn test_ntrup761_kats() {
+    check_kats(&NTRUP761);
+}